@@ -0,0 +1,130 @@
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use near_sdk::serde_json::{json, Value};
+use near_sdk::{env, AccountId};
+
+// Events follow NEP-297: `EVENT_JSON:{"standard", "version", "event", "data"}`
+pub const EVENT_STANDARD: &str = "game_items";
+pub const EVENT_VERSION: &str = "1.0.0";
+// Added to every `data` entry; bump it whenever the fields of any event change
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+// Every event the contract emits, each with its own data shape
+pub enum Event {
+    Mint(MintData),
+    Listing(ListingData),
+    MintingLocked(MintingLockedData),
+    Approve(ApprovalData),
+    Reveal(RevealData),
+    Transfer(TransferData),
+    MetadataUpdate(MetadataUpdateData),
+    Rescue(RescueData),
+    SupplyCorrected(SupplyCorrectedData),
+}
+
+impl Event {
+    fn name(&self) -> &'static str {
+        match self {
+            Event::Mint(_) => "mint",
+            Event::Listing(_) => "listing",
+            Event::MintingLocked(_) => "minting_locked",
+            Event::Approve(_) => "approve",
+            Event::Reveal(_) => "reveal",
+            Event::Transfer(_) => "transfer",
+            Event::MetadataUpdate(_) => "metadata_update",
+            Event::Rescue(_) => "rescue",
+            Event::SupplyCorrected(_) => "supply_corrected",
+        }
+    }
+
+    fn data(&self) -> Value {
+        match self {
+            Event::Mint(data) => json!(data),
+            Event::Listing(data) => json!(data),
+            Event::MintingLocked(data) => json!(data),
+            Event::Approve(data) => json!(data),
+            Event::Reveal(data) => json!(data),
+            Event::Transfer(data) => json!(data),
+            Event::MetadataUpdate(data) => json!(data),
+            Event::Rescue(data) => json!(data),
+            Event::SupplyCorrected(data) => json!(data),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RevealData {
+    pub token_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintData {
+    pub owner_id: AccountId,
+    pub token_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ListingData {
+    pub token_id: String,
+    pub seller_id: AccountId,
+    pub price: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintingLockedData {
+    pub owner_id: AccountId,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ApprovalData {
+    pub owner_id: AccountId,
+    pub account_id: AccountId,
+    pub token_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TransferData {
+    pub old_owner_id: AccountId,
+    pub new_owner_id: AccountId,
+    pub token_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MetadataUpdateData {
+    pub token_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RescueData {
+    pub new_owner_id: AccountId,
+    pub token_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SupplyCorrectedData {
+    pub old_total_minted: u64,
+    pub total_minted: u64,
+    pub total_burned: u64,
+    pub total_supply: u64,
+}
+
+pub(crate) fn emit_event(event: Event) {
+    let mut data = event.data();
+    data["schema_version"] = json!(EVENT_SCHEMA_VERSION);
+    let payload = json!({
+        "standard": EVENT_STANDARD,
+        "version": EVENT_VERSION,
+        "event": event.name(),
+        "data": [data],
+    });
+    env::log_str(&format!("EVENT_JSON:{}", payload));
+}
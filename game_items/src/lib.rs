@@ -1,15 +1,516 @@
+// `mint` takes its optional item properties as separate arguments, and near_bindgen copies
+// that signature into generated code that can't be annotated directly
+#![allow(clippy::too_many_arguments)]
+
+use ed25519_dalek::Verifier;
+use miniz_oxide::{deflate, inflate};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, LookupMap, UnorderedSet};
-use near_sdk::{env, near_bindgen, AccountId, PanicOnDefault, Promise};
+use near_sdk::collections::{LookupMap, LookupSet, TreeMap, UnorderedSet};
+use near_sdk::json_types::{Base64VecU8, U128};
+use near_sdk::{
+    env, ext_contract, near_bindgen, AccountId, Balance, CurveType, Gas, PanicOnDefault, Promise,
+    PromiseOrValue, PromiseResult, PublicKey,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Bound;
+
+mod events;
+use events::{
+    emit_event, ApprovalData, Event, ListingData, MetadataUpdateData, MintData, MintingLockedData,
+    RescueData, RevealData, SupplyCorrectedData, TransferData,
+};
+
+const DEFAULT_RECENT_MINTS_CAPACITY: u32 = 10;
+const MAX_RECENT_MINTS_CAPACITY: u32 = 100;
+const DEFAULT_PAGE_LIMIT: u64 = 50;
+const MIN_SEARCH_LENGTH: usize = 3;
+const MAX_EXPORT_LIMIT: u64 = 100;
+const BUNDLE_ITEM_TYPE: &str = "bundle";
+// Royalties are expressed in basis points (1/100 of a percent)
+const MAX_TOTAL_ROYALTY_BPS: u32 = 5_000;
+const MAX_ROYALTY_RECIPIENTS: usize = 10;
+const MAX_HOLDER_ROYALTY_DEPTH: u8 = 5;
+// Attached items moved along with one parent, bounding the gas of a cascading transfer
+const DEFAULT_MAX_ATTACHED: u32 = 20;
+const TRANSFER_ACTION: &str = "transfer";
+const REDEEM_ACTION: &str = "redeem";
+const MAX_ATTACHED_LIMIT: u32 = 100;
+// Operators approved on one item at a time, bounding the item's record size
+const DEFAULT_MAX_APPROVALS_PER_TOKEN: u32 = 10;
+const MAX_APPROVALS_LIMIT: u32 = 100;
+const METADATA_COMPRESSION_LEVEL: u8 = 9;
+const MAX_DECIMALS: u8 = 24;
+const DEFAULT_MAX_METADATA_BYTES: u32 = 2048;
+// Longest a rental may run ahead of the current block, extensions included
+const MAX_RENTAL_NS: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+// Defaults for the owner-configurable gas of cross-contract calls, and the range each
+// setting must stay within
+const DEFAULT_GAS_FOR_NFT_ON_TRANSFER: Gas = Gas(25_000_000_000_000);
+const DEFAULT_GAS_FOR_RESOLVE_TRANSFER: Gas = Gas(10_000_000_000_000);
+const DEFAULT_GAS_FOR_ON_ITEM_TRANSFER: Gas = Gas(5_000_000_000_000);
+const MIN_HOOK_GAS: Gas = Gas(1_000_000_000_000);
+const MAX_HOOK_GAS: Gas = Gas(100_000_000_000_000);
+const GAS_FOR_MIGRATE: Gas = Gas(50_000_000_000_000);
+
+// Receiver side of `nft_transfer_call` (NEP-171)
+#[ext_contract(ext_nft_receiver)]
+pub trait NonFungibleTokenReceiver {
+    /// Returns true if the item should be returned to `previous_owner_id`.
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: String,
+        msg: String,
+    ) -> PromiseOrValue<bool>;
+}
+
+// Operator side of `nft_approve` (NEP-178)
+#[ext_contract(ext_nft_approval_receiver)]
+pub trait NonFungibleTokenApprovalReceiver {
+    fn nft_on_approve(
+        &mut self,
+        token_id: String,
+        owner_id: AccountId,
+        approval_id: u64,
+        msg: String,
+    ) -> PromiseOrValue<String>;
+}
+
+// Off-contract mirrors notified of every ownership change
+#[ext_contract(ext_transfer_observer)]
+pub trait TransferObserver {
+    fn on_item_transfer(&mut self, item_id: String, from: AccountId, to: AccountId);
+}
 
 // Metadata for game items
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct GameItem {
     pub id: String,
     pub owner_id: AccountId,
+    // Account that minted the item; unlike `owner_id` it never changes
+    pub creator_id: AccountId,
+    pub item_type: String,
+    pub rarity: Rarity,
     pub metadata: String, // JSON string with item details
+    // Compressed items keep `metadata` empty in storage and hold it deflated here instead;
+    // views always return the decompressed `metadata`
+    pub compressed: bool,
+    pub compressed_metadata: Option<Base64VecU8>,
+    // Whether the item is one of a kind or a stack of interchangeable units
+    pub kind: ItemKind,
+    // Bundle token currently wrapping this item
+    pub bundled_in: Option<String>,
+    // Parent item this one is attached to; attached items move with their parent
+    pub attached_to: Option<String>,
+    // Secondary-sale royalty in basis points per recipient
+    pub royalty: HashMap<AccountId, u16>,
+    // Number of times the item changed hands
+    pub transfer_count: u32,
+    // Previous holders, oldest first; only the last `MAX_HOLDER_ROYALTY_DEPTH` are kept
+    pub transfer_log: Vec<AccountId>,
+    // Set once the placeholder metadata of a blind-box item has been replaced
+    pub revealed: bool,
+    // Locked by its holder; locked items can't change hands
+    pub locked: bool,
+    // Frozen by the contract owner; the metadata can never change again
+    pub metadata_locked: bool,
+    // Bound to its holder by the contract owner; soulbound items can't change hands
+    pub soulbound: bool,
+    // Accounts allowed to transfer the item on the holder's behalf, with their approval ids;
+    // cleared whenever the item changes hands
+    pub approved_account_ids: HashMap<AccountId, u64>,
+    pub next_approval_id: u64,
+    // sha256 of `metadata`, kept in sync by every legitimate metadata write
+    pub content_hash: [u8; 32],
+    // Reward credited to the holder when the item is burned through `redeem`
+    pub redeem_value: Option<U128>,
+    // Lent out by its holder; rented items can't change hands until reclaimed
+    pub rental: Option<Rental>,
+    // Rolling hash over every transfer while provenance tracking is on; all zeroes before
+    // the first tracked transfer
+    pub provenance_hash: [u8; 32],
+}
+
+impl GameItem {
+    /// Build a freshly minted item, hashing its metadata. The caller becomes its creator.
+    pub fn new(
+        id: String,
+        owner_id: AccountId,
+        item_type: String,
+        metadata: String,
+        kind: ItemKind,
+    ) -> Self {
+        Self {
+            content_hash: hash_metadata(&metadata),
+            id,
+            owner_id,
+            creator_id: env::predecessor_account_id(),
+            item_type,
+            rarity: Rarity::Common,
+            metadata,
+            compressed: false,
+            compressed_metadata: None,
+            kind,
+            royalty: HashMap::new(),
+            bundled_in: None,
+            attached_to: None,
+            transfer_count: 0,
+            transfer_log: Vec::new(),
+            revealed: false,
+            locked: false,
+            metadata_locked: false,
+            soulbound: false,
+            approved_account_ids: HashMap::new(),
+            next_approval_id: 0,
+            redeem_value: None,
+            rental: None,
+            provenance_hash: [0; 32],
+        }
+    }
+
+    /// Number of units the item stands for; 1 for a unique item.
+    pub fn quantity(&self) -> u64 {
+        match self.kind {
+            ItemKind::Unique => 1,
+            ItemKind::Stackable { quantity } => quantity,
+        }
+    }
+
+    fn is_held_or_approved(&self, account_id: &AccountId) -> bool {
+        &self.owner_id == account_id || self.approved_account_ids.contains_key(account_id)
+    }
+
+    // Replace the metadata, keeping the content hash and storage form in sync
+    fn set_metadata(&mut self, metadata: String) {
+        self.content_hash = hash_metadata(&metadata);
+        self.metadata = metadata;
+        if self.compressed {
+            self.compress_metadata();
+        }
+    }
+
+    // Move `metadata` into its deflated stored form
+    fn compress_metadata(&mut self) {
+        let bytes = deflate::compress_to_vec(self.metadata.as_bytes(), METADATA_COMPRESSION_LEVEL);
+        self.compressed_metadata = Some(bytes.into());
+        self.metadata = String::new();
+        self.compressed = true;
+    }
+
+    // The `name` field of the metadata JSON, if there is one
+    fn name(&self) -> Option<String> {
+        let metadata = if self.compressed {
+            self.clone().decompressed().metadata
+        } else {
+            self.metadata.clone()
+        };
+        near_sdk::serde_json::from_str::<near_sdk::serde_json::Value>(&metadata)
+            .ok()?
+            .get("name")?
+            .as_str()
+            .map(str::to_string)
+    }
+
+    // Restore `metadata` of a stored item for reading; `compressed` is kept as a hint
+    fn decompressed(mut self) -> Self {
+        if let Some(bytes) = self.compressed_metadata.take() {
+            let raw = inflate::decompress_to_vec(&bytes.0).expect("Corrupt compressed metadata");
+            self.metadata = String::from_utf8(raw).expect("Metadata is not valid UTF-8");
+        }
+        self
+    }
+}
+
+fn assert_valid_royalty(royalty: &HashMap<AccountId, u16>) {
+    assert!(
+        royalty.len() <= MAX_ROYALTY_RECIPIENTS,
+        "Royalty can have at most {} recipients",
+        MAX_ROYALTY_RECIPIENTS
+    );
+    let total: u32 = royalty.values().map(|bps| *bps as u32).sum();
+    assert!(
+        total <= MAX_TOTAL_ROYALTY_BPS,
+        "Total royalty of {} bps exceeds the cap of {} bps",
+        total,
+        MAX_TOTAL_ROYALTY_BPS
+    );
+}
+
+// Cross-contract hooks get between 1 and 100 Tgas
+fn assert_hook_gas(name: &str, gas: Gas) {
+    assert!(
+        gas >= MIN_HOOK_GAS && gas <= MAX_HOOK_GAS,
+        "{} gas must be between {} and {}",
+        name,
+        MIN_HOOK_GAS.0,
+        MAX_HOOK_GAS.0
+    );
+}
+
+// Lifecycle methods are reserved for the contract account itself, not just its owner
+fn assert_self() {
+    assert_eq!(
+        env::predecessor_account_id(),
+        env::current_account_id(),
+        "Only the contract itself can call this method"
+    );
+}
+
+fn assert_transferable(item: &GameItem, new_owner_id: &AccountId) {
+    // `AccountId` already guarantees a well-formed receiver; a no-op transfer would
+    // still churn the owner indexes, so reject it outright
+    assert_ne!(&item.owner_id, new_owner_id, "Cannot transfer to self");
+    if let Some(reason) = transfer_blocker(item) {
+        panic!("{}", reason);
+    }
+}
+
+// Why the item itself can't change hands right now, if anything stops it
+fn transfer_blocker(item: &GameItem) -> Option<&'static str> {
+    if item.bundled_in.is_some() {
+        Some("Item is bundled and can't be transferred on its own")
+    } else if item.attached_to.is_some() {
+        Some("Item is attached to a parent and can't be transferred on its own")
+    } else if item.locked {
+        Some("Item is locked")
+    } else if item.soulbound {
+        Some("Item is soulbound")
+    } else if item.rental.is_some() {
+        Some("Item is rented out")
+    } else {
+        None
+    }
+}
+
+// sha256(prev_hash || from || to || timestamp), with the account ids as UTF-8 and the
+// timestamp as 8 little-endian bytes, so an indexer can replay the chain from events
+fn next_provenance_hash(
+    prev_hash: &[u8; 32],
+    from: &AccountId,
+    to: &AccountId,
+    timestamp: u64,
+) -> [u8; 32] {
+    let mut input = prev_hash.to_vec();
+    input.extend(from.as_bytes());
+    input.extend(to.as_bytes());
+    input.extend(timestamp.to_le_bytes());
+    env::sha256(&input).try_into().expect("sha256 is 32 bytes")
+}
+
+// Sort, then page. `UnorderedSet` swaps its last member into the hole a removal leaves, so
+// paging over one directly can skip or repeat ids between calls. Sorting loads the whole input
+// and costs O(n log n), so it's only used on a single owner's ids; collection-wide groups
+// are paged by key with `page_group` instead. A page holds at most 100 entries.
+fn paginate_stable<T: Ord>(
+    mut keys: Vec<T>,
+    from_index: Option<U128>,
+    limit: Option<u64>,
+) -> Vec<T> {
+    keys.sort();
+    let from_index = from_index.map(|index| index.0).unwrap_or(0) as usize;
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_EXPORT_LIMIT) as usize;
+    keys.into_iter().skip(from_index).take(limit).collect()
+}
+
+// Page the ids of one `group` of a (group, id) index in id order, starting right after
+// `after_id`. Costs O(log n + limit) however large the group is, and an id inserted before
+// the cursor never shifts the next page.
+fn page_group<G>(
+    index: &TreeMap<(G, String), ()>,
+    group: G,
+    after_id: Option<String>,
+    limit: Option<u64>,
+) -> Vec<String>
+where
+    G: Ord + Clone + BorshSerialize + BorshDeserialize,
+{
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_EXPORT_LIMIT) as usize;
+    let start = match after_id {
+        Some(after_id) => Bound::Excluded((group.clone(), after_id)),
+        None => Bound::Included((group.clone(), String::new())),
+    };
+    index
+        .range((start, Bound::Unbounded))
+        .take_while(|((entry_group, _), _)| *entry_group == group)
+        .take(limit)
+        .map(|((_, id), _)| id)
+        .collect()
+}
+
+fn new_owner_set(owner_id: &AccountId) -> UnorderedSet<String> {
+    // Every owner needs its own prefix, otherwise the sets share storage
+    let mut prefix = b"owner".to_vec();
+    prefix.extend(env::sha256(owner_id.as_bytes()));
+    UnorderedSet::new(prefix)
+}
+
+fn hash_metadata(metadata: &str) -> [u8; 32] {
+    env::sha256(metadata.as_bytes())
+        .try_into()
+        .expect("sha256 is 32 bytes")
+}
+
+// Unique items always move whole; stackable ones can be split, merged and partly transferred
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ItemKind {
+    Unique,
+    Stackable { quantity: u64 },
+}
+
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+    Epic,
+    Legendary,
+}
+
+// Shared by every id reserved in one `reserve_lazy` call
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LazyTemplate {
+    pub item_type: String,
+    pub metadata: String,
+}
+
+// Lifecycle of an item id, as seen by `get_item_status`
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ItemStatus {
+    Minted,
+    Reserved, // reserved via `reserve_lazy` but not yet claimed
+    Unknown,
+}
+
+// Slice of every sale shared among the item's last `depth` previous holders, weighted by
+// how many items each of them holds
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HolderRoyalty {
+    pub bps: u16,
+    pub depth: u8,
+}
+
+// Right to use an item until `until_ns` without owning it
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Rental {
+    pub renter_id: AccountId,
+    pub until_ns: u64,
+}
+
+// One item of an airdrop campaign
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AirdropEntry {
+    pub id: String,
+    pub receiver_id: AccountId,
+    pub item_type: String,
+    pub metadata: String,
+}
+
+// An item offered for sale in native NEAR
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Listing {
+    pub seller_id: AccountId,
+    pub price: U128,
+}
+
+// A page of `find_duplicate_names_after`; `next` is the cursor for the following page
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DuplicateNamesPage {
+    pub duplicates: Vec<(String, Vec<String>)>,
+    pub next: Option<(String, String)>,
+}
+
+// Progress of a `sweep_account` call
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SweepResult {
+    pub moved: u32,
+    pub remaining: u64,
+}
+
+// Progress of a `recount_supply` pass; `next` is the cursor for the following page and is
+// `None` once the whole id index has been scanned
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RecountProgress {
+    pub counted: u64,
+    pub next: Option<String>,
+}
+
+// Everything a front end needs to decide who may act on an item
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenAccess {
+    pub owner_id: AccountId,
+    pub approved_account_ids: HashMap<AccountId, u64>,
+    pub locked: bool,
+    pub soulbound: bool,
+}
+
+// NEP-171 view of an item for marketplaces; `royalty` is left out for items without one
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonToken {
+    pub token_id: String,
+    pub owner_id: AccountId,
+    pub creator_id: AccountId,
+    pub metadata: String,
+    pub approved_account_ids: HashMap<AccountId, u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub royalty: Option<HashMap<AccountId, u16>>,
+}
+
+impl From<GameItem> for JsonToken {
+    fn from(item: GameItem) -> Self {
+        let item = item.decompressed();
+        JsonToken {
+            token_id: item.id,
+            owner_id: item.owner_id,
+            creator_id: item.creator_id,
+            metadata: item.metadata,
+            approved_account_ids: item.approved_account_ids,
+            royalty: (!item.royalty.is_empty()).then_some(item.royalty),
+        }
+    }
+}
+
+// NEP-199 split of a sale amount by recipient, the seller's remainder included
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Payout {
+    pub payout: HashMap<AccountId, U128>,
+}
+
+// An account's NEP-145 storage deposit: `total` attached, of which `available` isn't
+// yet spent on items the account owns
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
 }
 
 #[near_bindgen]
@@ -17,125 +518,8030 @@ pub struct GameItem {
 pub struct GameItems {
     owner_id: AccountId,
     items: LookupMap<String, GameItem>,
+    // Every existing item id, ordered so that paging over it is stable
+    item_ids: TreeMap<String, ()>,
     owner_to_items: LookupMap<AccountId, UnorderedSet<String>>,
+    // Every account currently holding at least one item, ordered for stable paging
+    owner_ids: TreeMap<AccountId, ()>,
+    // (rarity, id) and (item type, id) entries, so each group pages in id order
+    rarity_index: TreeMap<(Rarity, String), ()>,
+    type_index: TreeMap<(String, String), ()>,
+    // Lazily reserved ids point at a shared metadata template instead of a full record
+    lazy_reserved: LookupMap<String, u64>,
+    lazy_templates: LookupMap<u64, LazyTemplate>,
+    next_template_id: u64,
+    // Bundle token id -> ids of the items it wraps
+    bundle_members: LookupMap<String, Vec<String>>,
+    // Items attached to each parent item
+    item_children: LookupMap<String, UnorderedSet<String>>,
+    max_attached: u32,
+    max_approvals_per_token: u32,
+    // Ids of the most recently minted items, oldest first, capped at `recent_mints_capacity`
+    recent_mints: Vec<String>,
+    recent_mints_capacity: u32,
+    // Royalty applied to new items minted without an explicit one
+    default_royalty: HashMap<AccountId, u16>,
+    // Fee charged on every non-exempt transfer, forwarded to the treasury
+    treasury_id: AccountId,
+    transfer_fee: Balance,
+    // When set, `transfer` only delivers to accounts that called `register_account`
+    require_registered_receiver: bool,
+    registered_accounts: LookupSet<AccountId>,
+    // When set, `nft_transfer_call` only delivers to these receiver contracts
+    receiver_allowlist: Option<UnorderedSet<AccountId>>,
+    // When set, `transfer` only offers the item; (receiver, item id) -> sender
+    pull_transfers: bool,
+    pending_transfers: LookupMap<(AccountId, String), AccountId>,
+    // The one pending offer of an item: id -> (receiver, fee payer, fee held until accepted)
+    pending_offers: LookupMap<String, (AccountId, AccountId, Balance)>,
+    // Cap on how often a single item may change hands; `None` means unlimited
+    max_transfers: Option<u32>,
+    // Redeemed rewards not yet claimed, per account
+    reward_balances: LookupMap<AccountId, Balance>,
+    // Item types accepted at mint time
+    categories: UnorderedSet<String>,
+    // Display decimals of fungible-like item types; quantities are always base units
+    decimals: LookupMap<String, u8>,
+    // Upper bound on the (uncompressed) metadata of any item
+    max_metadata_bytes: u32,
+    // Prefixes such as "https://" the metadata `media` URL must start with; empty allows any
+    allowed_media_schemes: Vec<String>,
+    listings: LookupMap<String, Listing>,
+    // Set once by `lock_minting`; there is deliberately no way to clear it
+    minting_locked: bool,
+    // One entry per named item, keyed (metadata `name`, item id) so ids sharing a name sit
+    // next to each other and no entry grows with the collection
+    name_index: TreeMap<(String, String), ()>,
+    // Contract notified, best effort, whenever an item changes hands
+    observer: Option<AccountId>,
+    // ed25519 keys accounts authorize relayed transfers with, and the nonces already used
+    signing_keys: LookupMap<AccountId, PublicKey>,
+    used_nonces: LookupSet<(AccountId, u64)>,
+    holder_royalty: Option<HolderRoyalty>,
+    // Lifetime counters; the current supply is always `total_minted - total_burned`
+    total_minted: u64,
+    total_burned: u64,
+    // Per-action cooldown in nanoseconds and, per account and action, the earliest
+    // block timestamp the action is allowed again
+    cooldown_durations: LookupMap<String, u64>,
+    cooldowns: LookupMap<(AccountId, String), u64>,
+    // Sale royalties credited per recipient, paid out by `withdraw_royalties`
+    royalties_accrued: LookupMap<AccountId, Balance>,
+    // Airdrop campaigns already run
+    campaigns: UnorderedSet<String>,
+    // NEP-145 storage deposits; items minted to a depositor are charged to their balance
+    storage_deposits: LookupMap<AccountId, StorageBalance>,
+    // Who paid for each item's storage and how much; burning the item refunds that account
+    storage_charges: LookupMap<String, (AccountId, Balance)>,
+    // Optional supply cap per item type, and how many items of each type were ever minted
+    type_caps: LookupMap<String, u64>,
+    type_minted: LookupMap<String, u64>,
+    // Extra time renters keep an expired rental before the holder can reclaim it, and the
+    // flat fee a renter pays the holder per `renter_extend`
+    rental_grace_ns: u64,
+    rental_extension_fee: Balance,
+    // When set, every transfer folds (from, to, timestamp) into the item's provenance hash
+    track_provenance: bool,
+    // Item types each non-owner minter may mint
+    minter_type_permissions: LookupMap<AccountId, UnorderedSet<String>>,
+    // Top-level metadata keys that metadata updates may never add, change or remove
+    locked_fields: UnorderedSet<String>,
+    // Accounts that may still send their items but can no longer receive new ones
+    receive_blocked: UnorderedSet<AccountId>,
+    // Bounds on listing prices in yoctoNEAR; 0 leaves that side unbounded
+    min_price: Balance,
+    max_price: Balance,
+    // Progress of the running `recount_supply`: the last id scanned, how many scanned ids
+    // still have an item record, whether the scan reached the end and the
+    // (total_minted, total_burned) it started from
+    recount_cursor: Option<String>,
+    recount_counted: u64,
+    recount_complete: bool,
+    recount_started_at: (u64, u64),
+    // Gas attached to `nft_on_transfer`, its `nft_resolve_transfer` callback and observer
+    // notifications
+    gas_for_nft_on_transfer: Gas,
+    gas_for_resolve_transfer: Gas,
+    gas_for_on_item_transfer: Gas,
+    // Accounts besides the contract owner that may stamp metadata through
+    // `transfer_with_metadata_update`, e.g. a marketplace
+    metadata_updaters: UnorderedSet<AccountId>,
 }
 
 #[near_bindgen]
 impl GameItems {
     #[init]
-    pub fn new(owner_id: AccountId) -> Self {
+    pub fn new(owner_id: AccountId, recent_mints_capacity: Option<u32>) -> Self {
+        let recent_mints_capacity = recent_mints_capacity.unwrap_or(DEFAULT_RECENT_MINTS_CAPACITY);
+        assert!(
+            recent_mints_capacity <= MAX_RECENT_MINTS_CAPACITY,
+            "Recent mints capacity can't exceed {}",
+            MAX_RECENT_MINTS_CAPACITY
+        );
+
         Self {
+            treasury_id: owner_id.clone(),
             owner_id,
             items: LookupMap::new(b"items".to_vec()),
+            item_ids: TreeMap::new(b"item_ids".to_vec()),
             owner_to_items: LookupMap::new(b"owner_to_items".to_vec()),
+            owner_ids: TreeMap::new(b"owner_ids".to_vec()),
+            rarity_index: TreeMap::new(b"rarity_index".to_vec()),
+            type_index: TreeMap::new(b"type_index".to_vec()),
+            lazy_reserved: LookupMap::new(b"lazy_reserved".to_vec()),
+            lazy_templates: LookupMap::new(b"lazy_templates".to_vec()),
+            next_template_id: 0,
+            bundle_members: LookupMap::new(b"bundle_members".to_vec()),
+            item_children: LookupMap::new(b"item_children".to_vec()),
+            max_attached: DEFAULT_MAX_ATTACHED,
+            max_approvals_per_token: DEFAULT_MAX_APPROVALS_PER_TOKEN,
+            recent_mints: Vec::new(),
+            recent_mints_capacity,
+            default_royalty: HashMap::new(),
+            transfer_fee: 0,
+            require_registered_receiver: false,
+            registered_accounts: LookupSet::new(b"registered_accounts".to_vec()),
+            receiver_allowlist: None,
+            pull_transfers: false,
+            pending_transfers: LookupMap::new(b"pending_transfers".to_vec()),
+            pending_offers: LookupMap::new(b"pending_offers".to_vec()),
+            max_transfers: None,
+            reward_balances: LookupMap::new(b"reward_balances".to_vec()),
+            categories: UnorderedSet::new(b"categories".to_vec()),
+            decimals: LookupMap::new(b"decimals".to_vec()),
+            max_metadata_bytes: DEFAULT_MAX_METADATA_BYTES,
+            allowed_media_schemes: Vec::new(),
+            listings: LookupMap::new(b"listings".to_vec()),
+            minting_locked: false,
+            name_index: TreeMap::new(b"name_index".to_vec()),
+            observer: None,
+            signing_keys: LookupMap::new(b"signing_keys".to_vec()),
+            used_nonces: LookupSet::new(b"used_nonces".to_vec()),
+            holder_royalty: None,
+            total_minted: 0,
+            total_burned: 0,
+            cooldown_durations: LookupMap::new(b"cooldown_durations".to_vec()),
+            cooldowns: LookupMap::new(b"cooldowns".to_vec()),
+            royalties_accrued: LookupMap::new(b"royalties_accrued".to_vec()),
+            campaigns: UnorderedSet::new(b"campaigns".to_vec()),
+            storage_deposits: LookupMap::new(b"storage_deposits".to_vec()),
+            storage_charges: LookupMap::new(b"storage_charges".to_vec()),
+            type_caps: LookupMap::new(b"type_caps".to_vec()),
+            type_minted: LookupMap::new(b"type_minted".to_vec()),
+            rental_grace_ns: 0,
+            rental_extension_fee: 0,
+            track_provenance: false,
+            minter_type_permissions: LookupMap::new(b"minter_type_permissions".to_vec()),
+            locked_fields: UnorderedSet::new(b"locked_fields".to_vec()),
+            receive_blocked: UnorderedSet::new(b"receive_blocked".to_vec()),
+            min_price: 0,
+            max_price: 0,
+            recount_cursor: None,
+            recount_counted: 0,
+            recount_complete: false,
+            recount_started_at: (0, 0),
+            gas_for_nft_on_transfer: DEFAULT_GAS_FOR_NFT_ON_TRANSFER,
+            gas_for_resolve_transfer: DEFAULT_GAS_FOR_RESOLVE_TRANSFER,
+            gas_for_on_item_transfer: DEFAULT_GAS_FOR_ON_ITEM_TRANSFER,
+            metadata_updaters: UnorderedSet::new(b"metadata_updaters".to_vec()),
         }
     }
 
-    /// Mint a new game item. Only the contract owner can mint.
-    pub fn mint(&mut self, id: String, metadata: String) {
+    /// Re-initialize the contract from its stored state after new code is deployed.
+    /// Lifecycle methods like this one are callable only by the contract account itself
+    /// (a transaction signed with its own key, or the self-call batched by `upgrade`).
+    /// Unlike owner-gated admin methods, being the configured owner isn't enough.
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        assert_self();
+        env::state_read().expect("Contract state is missing")
+    }
+
+    /// Deploy new contract code and run `migrate` on it in the same batch.
+    /// Only the contract account itself can upgrade, see `migrate`.
+    pub fn upgrade(&mut self, code: Base64VecU8) -> Promise {
+        assert_self();
+        Promise::new(env::current_account_id())
+            .deploy_contract(code.into())
+            .function_call("migrate".to_string(), Vec::new(), 0, GAS_FOR_MIGRATE)
+    }
+
+    /// Mint a new game item to the caller. The contract owner can mint any type; other
+    /// accounts only the types they were granted with `grant_mint_types`.
+    /// `item_type` must be a registered category.
+    /// Without `quantity` the item is `Unique`; pass one to mint a `Stackable` stack
+    /// (e.g. 50 arrows).
+    /// Without an explicit `royalty` the item inherits the contract's default royalty.
+    /// With `compress` the metadata is stored deflated, which pays off for large, repetitive
+    /// JSON: minting a 2.5 KB description-heavy payload takes ~0.6 KB of storage instead of
+    /// ~3.1 KB, index entries included.
+    /// `rarity` defaults to `Common`. Emits a `mint` event.
+    pub fn mint(
+        &mut self,
+        id: String,
+        item_type: String,
+        metadata: String,
+        quantity: Option<u64>,
+        royalty: Option<HashMap<AccountId, u16>>,
+        compress: Option<bool>,
+        rarity: Option<Rarity>,
+    ) {
         let sender = env::predecessor_account_id();
-        assert_eq!(sender, self.owner_id, "Only the owner can mint items");
-        assert!(!self.items.contains_key(&id), "Item ID already exists");
+        assert!(
+            self.may_mint(&sender, &item_type),
+            "Only the owner or a minter permitted for {} can mint it",
+            item_type
+        );
+        self.assert_minting_open();
+        self.assert_can_receive(&sender);
+        self.assert_registered_category(&item_type);
+        self.assert_valid_metadata(&metadata);
+        self.internal_count_type_mint(&item_type);
+        let kind = match quantity {
+            Some(quantity) => {
+                assert!(quantity > 0, "Quantity must be positive");
+                ItemKind::Stackable { quantity }
+            }
+            None => ItemKind::Unique,
+        };
 
-        let item = GameItem {
-            id: id.clone(),
-            owner_id: sender.clone(),
-            metadata,
+        let mut item = GameItem::new(id, sender, item_type, metadata, kind);
+        item.rarity = rarity.unwrap_or(Rarity::Common);
+        item.royalty = match royalty {
+            Some(royalty) => {
+                assert_valid_royalty(&royalty);
+                royalty
+            }
+            None => self.default_royalty.clone(),
         };
+        if compress.unwrap_or(false) {
+            item.compress_metadata();
+        }
+        let data = MintData {
+            owner_id: item.owner_id.clone(),
+            token_ids: vec![item.id.clone()],
+        };
+        self.internal_mint(item);
+        emit_event(Event::Mint(data));
+    }
 
-        self.items.insert(&id, &item);
+    /// Mint one item per entry straight to its receiver. Each `campaign_id` can run only
+    /// once, so an accidentally re-submitted airdrop is rejected as a whole. Only the
+    /// contract owner can airdrop. Emits a `mint` event per item.
+    pub fn airdrop(&mut self, campaign_id: String, entries: Vec<AirdropEntry>) {
+        self.assert_owner();
+        self.assert_minting_open();
+        assert!(
+            self.campaigns.insert(&campaign_id),
+            "Campaign {} has already been airdropped",
+            campaign_id
+        );
 
-        let mut items_set = self
-            .owner_to_items
-            .get(&sender)
-            .unwrap_or_else(|| UnorderedSet::new(b"owner".to_vec()));
-        items_set.insert(&id);
-        self.owner_to_items.insert(&sender, &items_set);
+        for entry in entries {
+            self.assert_can_receive(&entry.receiver_id);
+            self.assert_registered_category(&entry.item_type);
+            self.assert_valid_metadata(&entry.metadata);
+            self.internal_count_type_mint(&entry.item_type);
+            let mut item = GameItem::new(
+                entry.id.clone(),
+                entry.receiver_id.clone(),
+                entry.item_type,
+                entry.metadata,
+                ItemKind::Unique,
+            );
+            item.royalty = self.default_royalty.clone();
+            self.internal_mint(item);
+            emit_event(Event::Mint(MintData {
+                owner_id: entry.receiver_id,
+                token_ids: vec![entry.id],
+            }));
+        }
     }
 
-    /// Transfer a game item to a new owner.
-    pub fn transfer(&mut self, id: String, new_owner_id: AccountId) {
+    pub fn is_campaign_processed(&self, campaign_id: String) -> bool {
+        self.campaigns.contains(&campaign_id)
+    }
+
+    /// Permanently disable `mint`, `mint_and_list`, `airdrop`, `reserve_lazy` and `claim_lazy`,
+    /// as well as `bundle` and stack splits, which create new items too, proving the supply
+    /// is final. This can't be undone. Only the contract owner can lock
+    /// minting.
+    pub fn lock_minting(&mut self) {
+        self.assert_owner();
+        self.assert_minting_open();
+        self.minting_locked = true;
+
+        emit_event(Event::MintingLocked(MintingLockedData {
+            owner_id: self.owner_id.clone(),
+        }));
+    }
+
+    pub fn is_minting_locked(&self) -> bool {
+        self.minting_locked
+    }
+
+    /// Mint a unique item to the caller and list it for sale at `price` in one call.
+    /// Minting permissions are the same as for `mint`.
+    pub fn mint_and_list(&mut self, id: String, item_type: String, metadata: String, price: U128) {
+        self.mint(id.clone(), item_type, metadata, None, None, None, None);
+        self.internal_list(id, price);
+    }
+
+    /// List an item held by the caller for sale at `price` yoctoNEAR, replacing any
+    /// previous listing. The price must be within `price_bounds`. Emits a `listing` event.
+    pub fn list_item(&mut self, id: String, price: U128) {
+        self.internal_list(id, price);
+    }
+
+    /// Bound the price of new listings; 0 leaves that side unbounded. Existing listings are
+    /// unaffected. Only the contract owner can set price bounds.
+    pub fn set_price_bounds(&mut self, min_price: U128, max_price: U128) {
+        self.assert_owner();
+        assert!(
+            max_price.0 == 0 || min_price.0 <= max_price.0,
+            "Minimum price can't exceed the maximum"
+        );
+        self.min_price = min_price.0;
+        self.max_price = max_price.0;
+    }
+
+    /// Get the (minimum, maximum) listing price; 0 means unbounded.
+    pub fn price_bounds(&self) -> (U128, U128) {
+        (U128(self.min_price), U128(self.max_price))
+    }
+
+    /// Withdraw the caller's listing of an item.
+    pub fn delist_item(&mut self, id: String) {
+        let listing = self.listings.get(&id).expect("Item is not listed");
+        assert_eq!(
+            listing.seller_id,
+            env::predecessor_account_id(),
+            "Only the seller can delist this item"
+        );
+        self.listings.remove(&id);
+    }
+
+    /// Buy a listed item. The attached deposit must cover the price; royalties and the
+    /// previous-holder slice are credited out of the price for `withdraw_royalties`, the
+    /// seller receives the rest and any excess deposit is refunded.
+    #[payable]
+    pub fn buy_item(&mut self, id: String) {
+        let buyer = env::predecessor_account_id();
+        let listing = self.listings.get(&id).expect("Item is not listed");
+        let price = listing.price.0;
+        let deposit = env::attached_deposit();
+        assert!(
+            deposit >= price,
+            "Attached deposit {} is less than the price {}",
+            deposit,
+            price
+        );
+
+        let item = self.items.get(&id).expect("Item does not exist");
+        let mut seller_share = price;
+        for (recipient, amount) in self.internal_sale_payout(&item, price) {
+            seller_share -= amount;
+            let accrued = self.royalties_accrued.get(&recipient).unwrap_or(0);
+            self.royalties_accrued
+                .insert(&recipient, &(accrued + amount));
+        }
+        self.listings.remove(&id);
+        self.internal_transfer(item, &buyer);
+
+        if seller_share > 0 {
+            Promise::new(listing.seller_id).transfer(seller_share);
+        }
+        if deposit > price {
+            Promise::new(buyer).transfer(deposit - price);
+        }
+    }
+
+    pub fn get_listing(&self, id: String) -> Option<Listing> {
+        self.listings.get(&id)
+    }
+
+    pub fn royalties_owed(&self, account_id: AccountId) -> U128 {
+        U128(self.royalties_accrued.get(&account_id).unwrap_or(0))
+    }
+
+    /// Pay out the royalties the caller has accrued from sales.
+    pub fn withdraw_royalties(&mut self) -> Promise {
+        let sender = env::predecessor_account_id();
+        let owed = self.royalties_accrued.remove(&sender).unwrap_or(0);
+        assert!(owed > 0, "No royalties to withdraw");
+        Promise::new(sender).transfer(owed)
+    }
+
+    /// Reserve item ids without writing full records. Only the contract owner can reserve.
+    /// The template is stored once for the whole batch; `{id}` in it is replaced with the
+    /// item id when the item is claimed.
+    /// `item_type` must be a registered category.
+    pub fn reserve_lazy(&mut self, ids: Vec<String>, item_type: String, metadata_template: String) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the owner can reserve items"
+        );
+        self.assert_minting_open();
+        self.assert_registered_category(&item_type);
+        self.assert_valid_metadata(&metadata_template);
+
+        let template_id = self.next_template_id;
+        self.next_template_id += 1;
+        let template = LazyTemplate {
+            item_type,
+            metadata: metadata_template,
+        };
+        self.lazy_templates.insert(&template_id, &template);
+
+        for id in ids {
+            assert!(!self.items.contains_key(&id), "Item ID already exists");
+            assert!(
+                self.lazy_reserved.insert(&id, &template_id).is_none(),
+                "Item ID is already reserved"
+            );
+        }
+    }
+
+    /// Materialize a reserved item for `receiver`. Only the contract owner can claim.
+    pub fn claim_lazy(&mut self, id: String, receiver: AccountId) -> GameItem {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the owner can claim reserved items"
+        );
+        self.assert_minting_open();
+        self.assert_can_receive(&receiver);
+        let template_id = self
+            .lazy_reserved
+            .remove(&id)
+            .expect("Item ID is not reserved");
+        let template = self
+            .lazy_templates
+            .get(&template_id)
+            .expect("Metadata template does not exist");
+
+        let metadata = template.metadata.replace("{id}", &id);
+        self.assert_valid_metadata(&metadata);
+        self.internal_count_type_mint(&template.item_type);
+        let mut item = GameItem::new(id, receiver, template.item_type, metadata, ItemKind::Unique);
+        item.royalty = self.default_royalty.clone();
+
+        self.internal_mint(item.clone());
+        item
+    }
+
+    /// Wrap several items owned by the caller into a new bundle token owned by the caller.
+    /// Bundled items can't be transferred, locked or rented out individually until the
+    /// bundle is unwrapped, and items that couldn't change hands can't be bundled.
+    pub fn bundle(&mut self, item_ids: Vec<String>, bundle_id: String, metadata: String) -> String {
+        self.assert_minting_open();
+        let sender = env::predecessor_account_id();
+        assert!(!item_ids.is_empty(), "A bundle needs at least one item");
+        self.assert_valid_metadata(&metadata);
+
+        let mut members = Vec::with_capacity(item_ids.len());
+        for id in item_ids.iter() {
+            let item = self.items.get(id).expect("Item does not exist");
+            assert_eq!(item.owner_id, sender, "Only the owner can bundle this item");
+            assert!(item.bundled_in.is_none(), "Item is already bundled");
+            assert!(
+                members.iter().all(|member: &GameItem| member.id != item.id),
+                "Item {} is listed twice",
+                item.id
+            );
+            assert!(item.attached_to.is_none(), "Item is attached to a parent");
+            // Members follow the bundle to its buyer on `unbundle`, so they must be free to move
+            if let Some(reason) = transfer_blocker(&item) {
+                panic!("{}", reason);
+            }
+            if let Some(reason) = self.attached_transfer_blocker(&item) {
+                panic!("{}", reason);
+            }
+            members.push(item);
+        }
+        for mut item in members {
+            item.bundled_in = Some(bundle_id.clone());
+            self.items.insert(&item.id, &item);
+        }
+
+        let mut bundle = GameItem::new(
+            bundle_id.clone(),
+            sender,
+            BUNDLE_ITEM_TYPE.to_string(),
+            metadata,
+            ItemKind::Unique,
+        );
+        bundle.royalty = self.default_royalty.clone();
+        self.internal_mint(bundle);
+        self.bundle_members.insert(&bundle_id, &item_ids);
+        bundle_id
+    }
+
+    /// Burn a bundle token held by the caller and release its members to the caller. A bundle
+    /// that couldn't change hands or be burned (locked, soulbound, rented out or attached)
+    /// can't be unwrapped either.
+    pub fn unbundle(&mut self, bundle_id: String) -> Vec<String> {
         let sender = env::predecessor_account_id();
+        let bundle = self.items.get(&bundle_id).expect("Item does not exist");
+        assert_eq!(
+            bundle.owner_id, sender,
+            "Only the owner can unbundle this item"
+        );
+        let member_ids = self
+            .bundle_members
+            .remove(&bundle_id)
+            .expect("Item is not a bundle");
+        if let Some(reason) = transfer_blocker(&bundle).or_else(|| self.burn_blocker(&bundle)) {
+            panic!("{}", reason);
+        }
+
+        self.internal_burn(bundle);
+        for id in member_ids.iter() {
+            let mut item = self.items.get(id).expect("Item does not exist");
+            item.bundled_in = None;
+            // The bundle may have changed hands since it was created
+            if item.owner_id != sender {
+                self.internal_transfer(item, &sender);
+            } else {
+                self.items.insert(id, &item);
+            }
+        }
+        member_ids
+    }
+
+    /// Split `amount` off a stack into a new item `new_id` held by the same owner.
+    pub fn split(&mut self, id: String, new_id: String, amount: u64) {
+        let item = self.internal_stack_for_caller(&id);
+        self.internal_split_off(item, new_id, amount);
+    }
+
+    /// Merge the stack `from_id` into `into_id`, burning `from_id`. Both must be held by the
+    /// caller, share the same item type and be either both soulbound or both not.
+    pub fn merge(&mut self, into_id: String, from_id: String) {
+        assert_ne!(into_id, from_id, "Cannot merge an item into itself");
+        let mut into = self.internal_stack_for_caller(&into_id);
+        let from = self.internal_stack_for_caller(&from_id);
+        assert_eq!(
+            into.item_type, from.item_type,
+            "Only items of the same type can be merged"
+        );
+        assert_eq!(
+            into.soulbound, from.soulbound,
+            "Soulbound and transferable stacks can't be merged"
+        );
+        if let Some(reason) = self.burn_blocker(&from) {
+            panic!("{}", reason);
+        }
+
+        let quantity = into
+            .quantity()
+            .checked_add(from.quantity())
+            .expect("Quantity overflow");
+        into.kind = ItemKind::Stackable { quantity };
+        self.items.insert(&into_id, &into);
+        self.internal_burn(from);
+    }
+
+    /// Lock an item held by the caller so that it can't be transferred until unlocked.
+    pub fn lock_item(&mut self, id: String) {
+        self.internal_set_locked(id, true);
+    }
+
+    /// Unlock an item held by the caller.
+    pub fn unlock_item(&mut self, id: String) {
+        self.internal_set_locked(id, false);
+    }
+
+    /// Lend an item held by the caller to `renter_id` for `duration_ns` (at most 30 days).
+    /// The holder keeps ownership but can't move the item until it is reclaimed.
+    pub fn rent_out(&mut self, id: String, renter_id: AccountId, duration_ns: u64) {
         let mut item = self.items.get(&id).expect("Item does not exist");
-        assert_eq!(item.owner_id, sender, "Only the owner can transfer this item");
+        assert_eq!(
+            item.owner_id,
+            env::predecessor_account_id(),
+            "Only the owner can rent out this item"
+        );
+        assert_ne!(item.owner_id, renter_id, "Cannot rent an item to its owner");
+        if let Some(reason) = transfer_blocker(&item) {
+            panic!("{}", reason);
+        }
+        assert!(
+            duration_ns <= MAX_RENTAL_NS,
+            "Rentals can last at most {} ns",
+            MAX_RENTAL_NS
+        );
+        item.rental = Some(Rental {
+            renter_id,
+            until_ns: env::block_timestamp() + duration_ns,
+        });
+        self.items.insert(&id, &item);
+    }
 
-        // Remove item from current owner
-        let mut current_owner_items = self.owner_to_items.get(&sender).unwrap();
-        current_owner_items.remove(&id);
-        self.owner_to_items.insert(&sender, &current_owner_items);
+    /// Let the current renter extend their rental by `additional_ns`, paying the holder the
+    /// rental extension fee. The rental can't end more than 30 days from now; any excess
+    /// deposit is refunded.
+    #[payable]
+    pub fn renter_extend(&mut self, id: String, additional_ns: u64) {
+        let renter_id = env::predecessor_account_id();
+        let mut item = self.items.get(&id).expect("Item does not exist");
+        let rental = item.rental.as_mut().expect("Item is not rented out");
+        assert_eq!(
+            rental.renter_id, renter_id,
+            "Only the renter can extend this rental"
+        );
+        let now = env::block_timestamp();
+        assert!(
+            now < rental.until_ns.saturating_add(self.rental_grace_ns),
+            "Rental has expired"
+        );
+        rental.until_ns = rental
+            .until_ns
+            .checked_add(additional_ns)
+            .expect("Rental end overflow");
+        assert!(
+            rental.until_ns <= now.saturating_add(MAX_RENTAL_NS),
+            "Rentals can't end more than {} ns from now",
+            MAX_RENTAL_NS
+        );
 
-        // Add item to new owner
-        let mut new_owner_items = self
-            .owner_to_items
-            .get(&new_owner_id.clone())
-            .unwrap_or_else(|| UnorderedSet::new(b"owner".to_vec()));
-        new_owner_items.insert(&id);
-        self.owner_to_items.insert(&new_owner_id.clone(), &new_owner_items);
+        let fee = self.rental_extension_fee;
+        let deposit = env::attached_deposit();
+        assert!(
+            deposit >= fee,
+            "Attached deposit {} is less than the extension fee {}",
+            deposit,
+            fee
+        );
+        self.items.insert(&id, &item);
+        if fee > 0 {
+            Promise::new(item.owner_id).transfer(fee);
+        }
+        if deposit > fee {
+            Promise::new(renter_id).transfer(deposit - fee);
+        }
+    }
 
-        // Update ownership
-        item.owner_id = new_owner_id.clone();
+    /// End a rental of an item held by the caller. Only possible once the rental and the
+    /// grace period after it have both run out.
+    pub fn reclaim(&mut self, id: String) {
+        let mut item = self.items.get(&id).expect("Item does not exist");
+        assert_eq!(
+            item.owner_id,
+            env::predecessor_account_id(),
+            "Only the owner can reclaim this item"
+        );
+        let rental = item.rental.take().expect("Item is not rented out");
+        let reclaimable_at = rental.until_ns.saturating_add(self.rental_grace_ns);
+        assert!(
+            env::block_timestamp() >= reclaimable_at,
+            "Rental can't be reclaimed before {}",
+            reclaimable_at
+        );
         self.items.insert(&id, &item);
     }
 
-    /// Get details of a specific item by ID.
-    pub fn get_item(&self, id: String) -> GameItem {
-        self.items.get(&id).expect("Item does not exist")
+    pub fn get_rental(&self, id: String) -> Option<Rental> {
+        self.items.get(&id).and_then(|item| item.rental)
     }
 
-    /// Get all item IDs owned by a specific account.
-    pub fn get_items_by_owner(&self, owner_id: AccountId) -> Vec<String> {
-        self.owner_to_items
-            .get(&owner_id)
-            .map(|set| set.to_vec())
-            .unwrap_or_else(Vec::new)
+    /// Set how long after a rental ends the renter keeps the item before it can be
+    /// reclaimed, and the flat fee for each `renter_extend`. Only the contract owner can set
+    /// the rental policy.
+    pub fn set_rental_policy(&mut self, grace_ns: u64, extension_fee: U128) {
+        self.assert_owner();
+        self.rental_grace_ns = grace_ns;
+        self.rental_extension_fee = extension_fee.0;
     }
-}
 
-// Required for NEAR's testing framework
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use near_sdk::test_utils::{get_accounts, VMContextBuilder};
-    use near_sdk::{testing_env, AccountId};
+    /// Move an item to another registered category, where it counts against that type's
+    /// supply cap instead of the old one's. Only the contract owner can recategorize.
+    pub fn recategorize(&mut self, id: String, new_type: String) {
+        self.assert_owner();
+        self.assert_registered_category(&new_type);
+        let mut item = self.items.get(&id).expect("Item does not exist");
+        assert!(
+            !self.bundle_members.contains_key(&id),
+            "Bundles can't be recategorized"
+        );
+        assert_ne!(item.item_type, new_type, "Item already has this type");
 
-    fn get_context(predecessor: AccountId) -> VMContextBuilder {
-        let mut builder = VMContextBuilder::new();
-        builder.predecessor_account_id(predecessor);
-        builder
+        self.internal_count_type_mint(&new_type);
+        self.internal_uncount_type_mint(&item.item_type);
+        self.internal_remove_item_from_type(&item.item_type, &id);
+        self.internal_add_item_to_type(&new_type, &id);
+        item.item_type = new_type;
+        self.items.insert(&id, &item);
     }
 
-    #[test]
-    fn test_mint_and_transfer() {
-        let accounts = get_accounts();
-        let owner = accounts.owner.clone();
-        let alice = accounts.alice.clone();
-        let bob = accounts.bob.clone();
+    /// Attach `child_id` to `parent_id`, e.g. a scope to a rifle. The caller must hold both.
+    /// Attached items can't be transferred on their own and follow their parent instead.
+    pub fn attach_child(&mut self, parent_id: String, child_id: String) {
+        let sender = env::predecessor_account_id();
+        assert_ne!(parent_id, child_id, "Cannot attach an item to itself");
+        let parent = self
+            .items
+            .get(&parent_id)
+            .expect("Parent item does not exist");
+        let mut child = self
+            .items
+            .get(&child_id)
+            .expect("Child item does not exist");
+        assert!(
+            parent.owner_id == sender && child.owner_id == sender,
+            "Only the owner of both items can attach them"
+        );
+        assert!(
+            parent.bundled_in.is_none() && child.bundled_in.is_none(),
+            "Bundled items can't be attached"
+        );
+        assert!(
+            !self.bundle_members.contains_key(&parent_id)
+                && !self.bundle_members.contains_key(&child_id),
+            "Bundles can't be attached"
+        );
+        assert!(child.attached_to.is_none(), "Child is already attached");
+        assert!(
+            !child.locked && !child.soulbound,
+            "Locked or soulbound items can't be attached"
+        );
+        // Walking up from the parent must never reach the child, or the tree gets a cycle
+        let mut root_id = parent_id.clone();
+        let mut ancestor = parent.attached_to.clone();
+        while let Some(id) = ancestor {
+            assert_ne!(id, child_id, "Cannot attach an item to its own descendant");
+            ancestor = self.items.get(&id).and_then(|item| item.attached_to);
+            root_id = id;
+        }
+        let attached = self.internal_descendants(&root_id).len()
+            + 1
+            + self.internal_descendants(&child_id).len();
+        assert!(
+            attached <= self.max_attached as usize,
+            "{} would carry {} attached items, the limit is {}",
+            root_id,
+            attached,
+            self.max_attached
+        );
 
-        // Initialize contract
-        let mut context = get_context(owner.clone()).build();
-        testing_env!(context);
-        let mut contract = GameItems::new(owner.clone());
+        let mut children = self.item_children.get(&parent_id).unwrap_or_else(|| {
+            let mut prefix = b"children".to_vec();
+            prefix.extend(env::sha256(parent_id.as_bytes()));
+            UnorderedSet::new(prefix)
+        });
+        children.insert(&child_id);
+        self.item_children.insert(&parent_id, &children);
+        child.attached_to = Some(parent_id);
+        self.items.insert(&child_id, &child);
+    }
 
-        // Mint a new item
-        contract.mint("item1".to_string(), "{\"name\":\"Sword\",\"power\":10}".to_string());
-        let item = contract.get_item("item1".to_string());
-        assert_eq!(item.owner_id, owner.clone());
-        assert_eq!(item.id, "item1");
-        assert_eq!(item.metadata, "{\"name\":\"Sword\",\"power\":10}");
+    /// Detach `child_id` from `parent_id`; it stays with the caller as an independent item.
+    pub fn detach_child(&mut self, parent_id: String, child_id: String) {
+        let parent = self
+            .items
+            .get(&parent_id)
+            .expect("Parent item does not exist");
+        assert_eq!(
+            parent.owner_id,
+            env::predecessor_account_id(),
+            "Only the owner can detach this item"
+        );
+        let mut child = self
+            .items
+            .get(&child_id)
+            .expect("Child item does not exist");
+        assert_eq!(
+            child.attached_to.as_ref(),
+            Some(&parent_id),
+            "Item is not attached to this parent"
+        );
 
-        // Transfer the item to Alice
-        context = get_context(owner.clone()).build();
-        testing_env!(context);
-        contract.transfer("item1".to_string(), alice.clone());
-        let item = contract.get_item("item1".to_string());
-        assert_eq!(item.owner_id, alice.clone());
+        let mut children = self.item_children.get(&parent_id).unwrap();
+        children.remove(&child_id);
+        if children.is_empty() {
+            self.item_children.remove(&parent_id);
+        } else {
+            self.item_children.insert(&parent_id, &children);
+        }
+        child.attached_to = None;
+        self.items.insert(&child_id, &child);
+    }
 
-        // Attempt unauthorized transfer by Bob
-        context = get_context(bob.clone()).build();
-        testing_env!(context);
-        let result = std::panic::catch_unwind(|| {
-            contract.transfer("item1".to_string(), bob.clone());
-        });
-        assert!(result.is_err());
+    /// Set how many attached items a single transfer may carry along (at most 100).
+    /// Only the contract owner can set it.
+    pub fn set_max_attached(&mut self, max_attached: u32) {
+        self.assert_owner();
+        assert!(
+            max_attached <= MAX_ATTACHED_LIMIT,
+            "Attached item limit can't exceed {}",
+            MAX_ATTACHED_LIMIT
+        );
+        self.max_attached = max_attached;
+    }
+
+    pub fn get_max_attached(&self) -> u32 {
+        self.max_attached
+    }
+
+    /// Set how many operators may be approved on a single item (at most 100). Items already
+    /// over a lowered limit keep their approvals but can't take new ones.
+    /// Only the contract owner can set it.
+    pub fn set_max_approvals_per_token(&mut self, max_approvals: u32) {
+        self.assert_owner();
+        assert!(
+            max_approvals <= MAX_APPROVALS_LIMIT,
+            "Approval limit can't exceed {}",
+            MAX_APPROVALS_LIMIT
+        );
+        self.max_approvals_per_token = max_approvals;
+    }
+
+    pub fn get_max_approvals_per_token(&self) -> u32 {
+        self.max_approvals_per_token
+    }
+
+    pub fn get_children(&self, parent_id: String) -> Vec<String> {
+        self.item_children
+            .get(&parent_id)
+            .map(|set| set.to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Bind an item to its current holder, or release it. Only the contract owner can set it.
+    pub fn set_soulbound(&mut self, id: String, soulbound: bool) {
+        self.assert_owner();
+        let mut item = self.items.get(&id).expect("Item does not exist");
+        item.soulbound = soulbound;
+        self.items.insert(&id, &item);
+    }
+
+    /// Let `account_id` transfer an item held by the caller. With a `msg`, the operator's
+    /// `nft_on_approve` is called (NEP-178). Emits an `approve` event.
+    pub fn nft_approve(
+        &mut self,
+        token_id: String,
+        account_id: AccountId,
+        msg: Option<String>,
+    ) -> Option<Promise> {
+        let item = self.items.get(&token_id).expect("Item does not exist");
+        let owner_id = env::predecessor_account_id();
+        assert_eq!(
+            item.owner_id, owner_id,
+            "Only the owner can approve operators for this item"
+        );
+        let promise = self.internal_approve(item, &account_id, msg.as_ref());
+
+        emit_event(Event::Approve(ApprovalData {
+            owner_id,
+            account_id,
+            token_ids: vec![token_id],
+        }));
+        promise
+    }
+
+    /// Approve `account_id` on several items at once, e.g. to list a whole inventory.
+    /// Items that don't exist or aren't held by the caller are skipped. Returns the ids
+    /// that were approved and emits one `approve` event for all of them.
+    pub fn nft_approve_many(
+        &mut self,
+        token_ids: Vec<String>,
+        account_id: AccountId,
+        msg: Option<String>,
+    ) -> Vec<String> {
+        let owner_id = env::predecessor_account_id();
+        let mut approved = Vec::new();
+        for token_id in token_ids {
+            match self.items.get(&token_id) {
+                Some(item) if item.owner_id == owner_id => {
+                    self.internal_approve(item, &account_id, msg.as_ref());
+                    approved.push(token_id);
+                }
+                _ => {}
+            }
+        }
+
+        if !approved.is_empty() {
+            emit_event(Event::Approve(ApprovalData {
+                owner_id,
+                account_id,
+                token_ids: approved.clone(),
+            }));
+        }
+        approved
+    }
+
+    /// Withdraw an operator's approval on an item held by the caller.
+    pub fn nft_revoke(&mut self, token_id: String, account_id: AccountId) {
+        let mut item = self.items.get(&token_id).expect("Item does not exist");
+        assert_eq!(
+            item.owner_id,
+            env::predecessor_account_id(),
+            "Only the owner can revoke approvals for this item"
+        );
+        if item.approved_account_ids.remove(&account_id).is_some() {
+            self.items.insert(&token_id, &item);
+        }
+    }
+
+    /// Withdraw every approval on an item held by the caller.
+    pub fn nft_revoke_all(&mut self, token_id: String) {
+        let mut item = self.items.get(&token_id).expect("Item does not exist");
+        assert_eq!(
+            item.owner_id,
+            env::predecessor_account_id(),
+            "Only the owner can revoke approvals for this item"
+        );
+        if !item.approved_account_ids.is_empty() {
+            item.approved_account_ids.clear();
+            self.items.insert(&token_id, &item);
+        }
+    }
+
+    /// Check whether `approved_account_id` may transfer an item, optionally under a specific
+    /// approval id.
+    pub fn nft_is_approved(
+        &self,
+        token_id: String,
+        approved_account_id: AccountId,
+        approval_id: Option<u64>,
+    ) -> bool {
+        let item = self.items.get(&token_id).expect("Item does not exist");
+        match (
+            item.approved_account_ids.get(&approved_account_id),
+            approval_id,
+        ) {
+            (Some(id), Some(approval_id)) => *id == approval_id,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    /// Replace the metadata of an item. Keys in `locked_fields` must keep their values.
+    /// Only the contract owner can update metadata. Emits a `metadata_update` event.
+    pub fn update_metadata(&mut self, id: String, metadata: String) {
+        self.assert_owner();
+        let mut item = self.items.get(&id).expect("Item does not exist");
+        self.assert_locked_fields_unchanged(&item, &metadata);
+        self.internal_replace_metadata(&mut item, metadata);
+        self.items.insert(&id, &item);
+    }
+
+    /// Shallow-merge a JSON object into an item's metadata: keys in `patch` overwrite,
+    /// all others are kept. Keys in `locked_fields` can't be patched. Only the contract owner
+    /// can patch metadata. Emits a `metadata_update` event.
+    pub fn patch_metadata(&mut self, id: String, patch: String) {
+        self.assert_owner();
+        let mut item = self.items.get(&id).expect("Item does not exist");
+        let patch: near_sdk::serde_json::Map<String, near_sdk::serde_json::Value> =
+            near_sdk::serde_json::from_str(&patch).expect("Patch must be a JSON object");
+        let mut merged: near_sdk::serde_json::Map<String, near_sdk::serde_json::Value> =
+            near_sdk::serde_json::from_str(&item.clone().decompressed().metadata)
+                .expect("Item metadata is not a JSON object");
+        merged.extend(patch);
+
+        let metadata = near_sdk::serde_json::Value::Object(merged).to_string();
+        self.assert_locked_fields_unchanged(&item, &metadata);
+        self.internal_replace_metadata(&mut item, metadata);
+        self.items.insert(&id, &item);
+    }
+
+    /// Freeze an item's metadata for good; no update, patch or reveal can change it
+    /// afterwards. Only the contract owner can lock metadata.
+    pub fn lock_metadata(&mut self, id: String) {
+        self.assert_owner();
+        let mut item = self.items.get(&id).expect("Item does not exist");
+        item.metadata_locked = true;
+        self.items.insert(&id, &item);
+    }
+
+    /// Replace the placeholder metadata of a blind-box item with its real metadata. Each
+    /// item can be revealed only once. Only the contract owner can reveal. Emits a
+    /// `metadata_update` and a `reveal` event.
+    pub fn reveal(&mut self, id: String, real_metadata: String) {
+        self.assert_owner();
+        let mut item = self.items.get(&id).expect("Item does not exist");
+        assert!(!item.revealed, "Item is already revealed");
+        self.internal_replace_metadata(&mut item, real_metadata);
+        item.revealed = true;
+        self.items.insert(&id, &item);
+
+        emit_event(Event::Reveal(RevealData {
+            token_ids: vec![id],
+        }));
+    }
+
+    /// Reveal many blind-box items in one call from (id, real metadata) pairs. Items that
+    /// are already revealed are skipped and returned; any other failure reverts the whole
+    /// batch. Only the contract owner can reveal. Emits a `metadata_update` event per item
+    /// and one `reveal` event for all newly revealed items.
+    pub fn batch_reveal(&mut self, reveals: Vec<(String, String)>) -> Vec<String> {
+        self.assert_owner();
+        let mut revealed = Vec::new();
+        let mut skipped = Vec::new();
+        for (id, real_metadata) in reveals {
+            let mut item = self.items.get(&id).expect("Item does not exist");
+            if item.revealed {
+                skipped.push(id);
+                continue;
+            }
+            self.internal_replace_metadata(&mut item, real_metadata);
+            item.revealed = true;
+            self.items.insert(&id, &item);
+            revealed.push(id);
+        }
+
+        if !revealed.is_empty() {
+            emit_event(Event::Reveal(RevealData {
+                token_ids: revealed,
+            }));
+        }
+        skipped
+    }
+
+    /// Set the reward an item is worth when redeemed; `None` makes it worth nothing.
+    /// Only the contract owner can set redeem values.
+    pub fn set_redeem_value(&mut self, id: String, redeem_value: Option<U128>) {
+        self.assert_owner();
+        let mut item = self.items.get(&id).expect("Item does not exist");
+        item.redeem_value = redeem_value;
+        self.items.insert(&id, &item);
+    }
+
+    /// Burn an item held by the caller and credit its redeem value to the caller's reward
+    /// balance. Returns the amount credited.
+    pub fn redeem(&mut self, id: String) -> U128 {
+        let sender = env::predecessor_account_id();
+        let item = self.items.get(&id).expect("Item does not exist");
+        assert_eq!(item.owner_id, sender, "Only the owner can redeem this item");
+        if let Some(reason) = self.burn_blocker(&item) {
+            panic!("{}", reason);
+        }
+
+        self.check_and_set_cooldown(&sender, REDEEM_ACTION);
+        let value = item.redeem_value.map(|value| value.0).unwrap_or(0);
+        self.internal_burn(item);
+        if value > 0 {
+            let balance = self.reward_balances.get(&sender).unwrap_or(0);
+            self.reward_balances.insert(
+                &sender,
+                &balance.checked_add(value).expect("Reward balance overflow"),
+            );
+        }
+        U128(value)
+    }
+
+    /// Pay out the caller's whole reward balance.
+    pub fn claim_rewards(&mut self) -> Promise {
+        let sender = env::predecessor_account_id();
+        let balance = self.reward_balances.remove(&sender).unwrap_or(0);
+        assert!(balance > 0, "No rewards to claim");
+        Promise::new(sender).transfer(balance)
+    }
+
+    pub fn get_reward_balance(&self, account_id: AccountId) -> U128 {
+        U128(self.reward_balances.get(&account_id).unwrap_or(0))
+    }
+
+    /// Transfer a game item to a new owner. The holder or an approved operator can transfer;
+    /// the contract owner is no exception and needs `force_transfer` for items it doesn't hold.
+    /// Requires `transfer_fee` to be attached unless the sender is the contract owner;
+    /// the fee goes to the treasury and any excess deposit is refunded.
+    /// With pull transfers enabled the item only becomes pending: it stays with the sender
+    /// until the receiver calls `accept_transfer`. An item can be offered to one receiver
+    /// at a time, and the fee is held until the offer is accepted and refunded otherwise.
+    /// Emits a `transfer` event once the item changes hands.
+    #[payable]
+    pub fn transfer(&mut self, id: String, new_owner_id: AccountId) {
+        if self.pull_transfers {
+            let (item, fee) = self.internal_authorize_holder_transfer(&id, &new_owner_id);
+            self.internal_offer_transfer(item, new_owner_id, fee);
+        } else {
+            self.internal_transfer_by_holder(&id, &new_owner_id);
+        }
+    }
+
+    /// Transfer an item like `transfer`, but only if its current metadata still hashes to
+    /// `expected_content_hash` (sha256, as in `content_hash`). Lets a trade settle on the
+    /// item exactly as it was advertised, even if the metadata was swapped in between.
+    #[payable]
+    pub fn transfer_if_unchanged(
+        &mut self,
+        id: String,
+        new_owner_id: AccountId,
+        expected_content_hash: [u8; 32],
+    ) {
+        let item = self
+            .items
+            .get(&id)
+            .expect("Item does not exist")
+            .decompressed();
+        assert!(
+            hash_metadata(&item.metadata) == expected_content_hash,
+            "Item metadata has changed since it was advertised"
+        );
+        self.transfer(id, new_owner_id);
+    }
+
+    /// Transfer an item and replace its metadata in one call, e.g. for a marketplace stamping
+    /// provenance at the moment of sale. The caller must be the contract owner or a metadata
+    /// updater, and also the holder or an approved operator of the item; the fee is the same
+    /// as for `transfer`. If either step fails nothing changes. Not available while pull
+    /// transfers are enabled. Emits a `metadata_update` and a `transfer` event.
+    #[payable]
+    pub fn transfer_with_metadata_update(
+        &mut self,
+        id: String,
+        new_owner_id: AccountId,
+        metadata: String,
+    ) {
+        assert!(
+            !self.pull_transfers,
+            "Pull transfers are enabled; use transfer and update the metadata separately"
+        );
+        let sender = env::predecessor_account_id();
+        assert!(
+            sender == self.owner_id || self.metadata_updaters.contains(&sender),
+            "Only the contract owner or a metadata updater can update metadata"
+        );
+        let (mut item, fee) = self.internal_authorize_holder_transfer(&id, &new_owner_id);
+        self.internal_pay_transfer_fee(fee);
+        self.assert_locked_fields_unchanged(&item, &metadata);
+        self.internal_replace_metadata(&mut item, metadata);
+        self.internal_transfer(item, &new_owner_id);
+    }
+
+    /// Transfer `amount` units of an item like `transfer`. A unique item, or the whole of a
+    /// stack, moves as is; otherwise `amount` is split off the stack into a new stack
+    /// `new_id` that goes to `new_owner_id` while the rest stays with the sender.
+    /// Not available while pull transfers are enabled.
+    #[payable]
+    pub fn transfer_quantity(
+        &mut self,
+        id: String,
+        new_owner_id: AccountId,
+        amount: u64,
+        new_id: String,
+    ) {
+        assert!(
+            !self.pull_transfers,
+            "Pull transfers are enabled; split the stack and use transfer instead"
+        );
+        let (item, fee) = self.internal_authorize_holder_transfer(&id, &new_owner_id);
+        self.internal_pay_transfer_fee(fee);
+        if amount == item.quantity() {
+            self.internal_transfer(item, &new_owner_id);
+            return;
+        }
+        assert!(
+            matches!(item.kind, ItemKind::Stackable { .. }),
+            "Only stackable items can be partly transferred"
+        );
+        // The new stack starts out clean, so the source has to be free to move in its place
+        assert_transferable(&item, &new_owner_id);
+        self.assert_within_transfer_limit(&item);
+        assert!(!self.has_children(&id), "Detach the item's children first");
+
+        self.internal_split_off(item, new_id.clone(), amount);
+        let split_off = self.items.get(&new_id).expect("Item does not exist");
+        self.internal_transfer(split_off, &new_owner_id);
+    }
+
+    /// Register the ed25519 key the caller signs relayed transfers with, replacing any
+    /// previous key.
+    pub fn set_signing_key(&mut self, public_key: PublicKey) {
+        assert!(
+            matches!(public_key.curve_type(), CurveType::ED25519),
+            "Signing key must be an ed25519 key"
+        );
+        self.signing_keys
+            .insert(&env::predecessor_account_id(), &public_key);
+    }
+
+    pub fn get_signing_key(&self, account_id: AccountId) -> Option<PublicKey> {
+        self.signing_keys.get(&account_id)
+    }
+
+    /// Transfer an item on behalf of its holder, who authorized it off-chain by signing
+    /// `transfer_message(id, to, nonce)` with their registered key. Any account can submit
+    /// the signature; each nonce works once per holder. The submitter settles the transfer
+    /// fee, while the holder's transfer cooldown applies as if they had sent it themselves.
+    /// With pull transfers enabled the item only becomes pending, as with `transfer`.
+    #[payable]
+    pub fn transfer_with_signature(
+        &mut self,
+        id: String,
+        to: AccountId,
+        nonce: u64,
+        signature: Base64VecU8,
+    ) {
+        let item = self.items.get(&id).expect("Item does not exist");
+        let holder = item.owner_id.clone();
+        let public_key = self
+            .signing_keys
+            .get(&holder)
+            .expect("Holder has no signing key");
+        assert!(
+            !self.used_nonces.contains(&(holder.clone(), nonce)),
+            "Nonce has already been used"
+        );
+
+        let message = self.transfer_message(id, to.clone(), nonce);
+        let public_key = ed25519_dalek::PublicKey::from_bytes(&public_key.as_bytes()[1..])
+            .expect("Invalid signing key");
+        let verified = ed25519_dalek::Signature::from_bytes(&signature.0)
+            .map(|signature| public_key.verify(message.as_bytes(), &signature).is_ok())
+            .unwrap_or(false);
+        assert!(verified, "Invalid signature");
+
+        self.check_and_set_cooldown(&holder, TRANSFER_ACTION);
+        self.used_nonces.insert(&(holder, nonce));
+        let fee = self.internal_settle_holder_transfer(&to);
+        if self.pull_transfers {
+            self.internal_offer_transfer(item, to, fee);
+        } else {
+            self.internal_pay_transfer_fee(fee);
+            self.internal_transfer(item, &to);
+        }
+    }
+
+    /// The exact message a holder signs to authorize `transfer_with_signature`. It names
+    /// this contract so a signature can't be replayed against another deployment.
+    pub fn transfer_message(&self, id: String, to: AccountId, nonce: u64) -> String {
+        format!(
+            "{}:transfer:{}:{}:{}",
+            env::current_account_id(),
+            id,
+            to,
+            nonce
+        )
+    }
+
+    /// Accept an item offered to the caller while pull transfers are enabled. The transfer
+    /// fee held with the offer goes to the treasury.
+    pub fn accept_transfer(&mut self, id: String) {
+        let receiver = env::predecessor_account_id();
+        let sender = self
+            .pending_transfers
+            .remove(&(receiver.clone(), id.clone()))
+            .expect("No pending transfer for this item");
+        let (_, _, fee) = self
+            .pending_offers
+            .remove(&id)
+            .expect("No pending transfer for this item");
+        let item = self.items.get(&id).expect("Item does not exist");
+        assert_eq!(
+            item.owner_id, sender,
+            "Pending transfer is no longer valid, reject it instead"
+        );
+        self.internal_pay_transfer_fee(fee);
+        self.internal_transfer(item, &receiver);
+    }
+
+    /// Decline an item offered to the caller; it simply stays with the sender and the
+    /// transfer fee is refunded to whoever paid it.
+    pub fn reject_transfer(&mut self, id: String) {
+        let receiver = env::predecessor_account_id();
+        assert!(
+            self.pending_transfers.contains_key(&(receiver, id.clone())),
+            "No pending transfer for this item"
+        );
+        self.internal_clear_offer(&id);
+    }
+
+    /// Withdraw the pending offer of an item, refunding the transfer fee to whoever paid it.
+    /// The holder or an approved account can cancel.
+    pub fn cancel_transfer(&mut self, id: String) {
+        let item = self.items.get(&id).expect("Item does not exist");
+        assert!(
+            item.is_held_or_approved(&env::predecessor_account_id()),
+            "Only the owner or an approved account can cancel this transfer"
+        );
+        assert!(
+            self.pending_offers.contains_key(&id),
+            "No pending transfer for this item"
+        );
+        self.internal_clear_offer(&id);
+    }
+
+    /// Get the sender of an item offered to `receiver_id`, if any.
+    pub fn get_pending_transfer(&self, receiver_id: AccountId, id: String) -> Option<AccountId> {
+        self.pending_transfers.get(&(receiver_id, id))
+    }
+
+    /// Require receivers to accept transfers instead of having items pushed to them.
+    /// Only the contract owner can toggle it.
+    pub fn set_pull_transfers(&mut self, enabled: bool) {
+        self.assert_owner();
+        self.pull_transfers = enabled;
+    }
+
+    /// Transfer an item to a receiver contract and notify it via `nft_on_transfer` (NEP-171).
+    /// The item comes back to the sender if the receiver asks for it or the call fails.
+    /// Fees and receiver checks are the same as for `transfer`, plus the receiver allowlist.
+    #[payable]
+    pub fn nft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        msg: String,
+    ) -> Promise {
+        if let Some(allowlist) = &self.receiver_allowlist {
+            assert!(
+                allowlist.contains(&receiver_id),
+                "Receiver {} is not on the allowlist",
+                receiver_id
+            );
+        }
+        let previous_owner_id = self.internal_transfer_by_holder(&token_id, &receiver_id);
+
+        ext_nft_receiver::ext(receiver_id.clone())
+            .with_static_gas(self.gas_for_nft_on_transfer)
+            .nft_on_transfer(
+                env::predecessor_account_id(),
+                previous_owner_id.clone(),
+                token_id.clone(),
+                msg,
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(self.gas_for_resolve_transfer)
+                    .nft_resolve_transfer(previous_owner_id, receiver_id, token_id),
+            )
+    }
+
+    /// Callback of `nft_transfer_call`. Returns true if the item stayed with the receiver.
+    #[private]
+    pub fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: String,
+    ) -> bool {
+        let return_item = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                near_sdk::serde_json::from_slice::<bool>(&value).unwrap_or(true)
+            }
+            _ => true,
+        };
+        if !return_item {
+            return true;
+        }
+
+        match self.items.get(&token_id) {
+            // The receiver may have already passed the item on; only undo our own transfer
+            Some(item) if item.owner_id == receiver_id => {
+                self.internal_move(item, &previous_owner_id);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Move an item to a new owner regardless of who holds it. Only the contract owner can
+    /// force a transfer, and no transfer fee is charged.
+    pub fn force_transfer(&mut self, id: String, new_owner_id: AccountId) {
+        self.assert_owner();
+        let item = self.items.get(&id).expect("Item does not exist");
+        self.internal_transfer(item, &new_owner_id);
+    }
+
+    /// Recover an item that was sent to the contract account by mistake, moving it to `to`.
+    /// Only the contract owner can rescue, and only items the contract itself holds.
+    pub fn rescue_to(&mut self, id: String, to: AccountId) {
+        self.assert_owner();
+        let item = self.items.get(&id).expect("Item does not exist");
+        assert_eq!(
+            item.owner_id,
+            env::current_account_id(),
+            "Only items held by the contract can be rescued"
+        );
+        self.internal_transfer(item, &to);
+        emit_event(Event::Rescue(RescueData {
+            new_owner_id: to,
+            token_ids: vec![id],
+        }));
+    }
+
+    /// Move up to `limit` items held by the contract owner to `new_owner`, skipping items
+    /// that can't change hands on their own (locked, bundled, attached, soulbound or out of
+    /// transfers) or whose attached items can't move with them. Only the contract owner can
+    /// reassign. Returns the number of items moved;
+    /// call again until it returns 0.
+    pub fn reassign_owner_items(&mut self, new_owner: AccountId, limit: u32) -> u32 {
+        self.assert_owner();
+        let owner_id = self.owner_id.clone();
+        self.internal_sweep(&owner_id, &new_owner, limit)
+    }
+
+    /// Move up to `limit` items (default 50) from a decommissioned account to `to`, skipping
+    /// items that can't change hands on their own (locked, bundled, attached, soulbound or
+    /// out of transfers); attached items move with their parent, so a parent whose attached
+    /// items can't move is skipped too. Only the contract owner can
+    /// sweep. Call again until `moved` is 0; `remaining` counts
+    /// what the account still holds, skipped items included.
+    pub fn sweep_account(
+        &mut self,
+        account_id: AccountId,
+        to: AccountId,
+        limit: Option<u32>,
+    ) -> SweepResult {
+        self.assert_owner();
+        let moved =
+            self.internal_sweep(&account_id, &to, limit.unwrap_or(DEFAULT_PAGE_LIMIT as u32));
+        let remaining = self
+            .owner_to_items
+            .get(&account_id)
+            .map(|set| set.len())
+            .unwrap_or(0);
+        SweepResult { moved, remaining }
+    }
+
+    /// Set the fee (in yoctoNEAR) charged on each transfer. Only the contract owner can set it.
+    pub fn set_transfer_fee(&mut self, transfer_fee: U128) {
+        self.assert_owner();
+        self.transfer_fee = transfer_fee.0;
+    }
+
+    /// Set the account receiving transfer fees. Only the contract owner can set it.
+    pub fn set_treasury(&mut self, treasury_id: AccountId) {
+        self.assert_owner();
+        self.treasury_id = treasury_id;
+    }
+
+    /// Opt the caller in to receiving items while `require_registered_receiver` is enabled.
+    pub fn register_account(&mut self) {
+        self.registered_accounts
+            .insert(&env::predecessor_account_id());
+    }
+
+    /// Check whether an account has called `register_account`.
+    pub fn is_registered(&self, account_id: AccountId) -> bool {
+        self.registered_accounts.contains(&account_id)
+    }
+
+    /// Add the attached deposit to the storage balance of `account_id` (NEP-145), defaulting
+    /// to the caller. Once an account has a balance, storage for items minted to it is
+    /// paid from `available`. Burning such an item refunds the freed bytes to the account
+    /// that paid for them, whoever holds the item by then, and never more than it was charged.
+    #[payable]
+    pub fn storage_deposit(&mut self, account_id: Option<AccountId>) -> StorageBalance {
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let deposit = env::attached_deposit();
+        let mut balance = self
+            .storage_deposits
+            .get(&account_id)
+            .unwrap_or(StorageBalance {
+                total: U128(0),
+                available: U128(0),
+            });
+        balance.total.0 += deposit;
+        balance.available.0 += deposit;
+        self.storage_deposits.insert(&account_id, &balance);
+        balance
+    }
+
+    /// Withdraw `amount` (default: all) of the caller's available storage balance.
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        let account_id = env::predecessor_account_id();
+        let mut balance = self
+            .storage_deposits
+            .get(&account_id)
+            .expect("No storage deposit for this account");
+        let amount = amount.map(|amount| amount.0).unwrap_or(balance.available.0);
+        assert!(
+            amount <= balance.available.0,
+            "Can't withdraw {}, only {} is available",
+            amount,
+            balance.available.0
+        );
+        balance.total.0 -= amount;
+        balance.available.0 -= amount;
+        self.storage_deposits.insert(&account_id, &balance);
+        if amount > 0 {
+            Promise::new(account_id).transfer(amount);
+        }
+        balance
+    }
+
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage_deposits.get(&account_id)
+    }
+
+    /// Stop `account_id` from receiving items through transfers, mints or airdrops, e.g.
+    /// while closing the account. It can still send the items it holds.
+    /// Only the contract owner can block accounts.
+    pub fn block_receiving(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.receive_blocked.insert(&account_id);
+    }
+
+    /// Let a blocked account receive items again. Only the contract owner can unblock
+    /// accounts.
+    pub fn unblock_receiving(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.receive_blocked.remove(&account_id);
+    }
+
+    pub fn is_receive_blocked(&self, account_id: AccountId) -> bool {
+        self.receive_blocked.contains(&account_id)
+    }
+
+    /// Let `account_id` replace metadata through `transfer_with_metadata_update`.
+    /// Only the contract owner can manage metadata updaters.
+    pub fn add_metadata_updater(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.metadata_updaters.insert(&account_id);
+    }
+
+    /// Revoke a metadata updater. Only the contract owner can manage metadata updaters.
+    pub fn remove_metadata_updater(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.metadata_updaters.remove(&account_id);
+    }
+
+    pub fn is_metadata_updater(&self, account_id: AccountId) -> bool {
+        self.metadata_updaters.contains(&account_id)
+    }
+
+    /// Require transfer receivers to be registered, guarding against typo'd accounts.
+    /// Only the contract owner can toggle it.
+    pub fn set_require_registered_receiver(&mut self, required: bool) {
+        self.assert_owner();
+        self.require_registered_receiver = required;
+    }
+
+    /// Register an item type that mints may use. Only the contract owner can manage
+    /// categories.
+    pub fn register_category(&mut self, category: String) {
+        self.assert_owner();
+        assert!(!category.is_empty(), "Category can't be empty");
+        self.categories.insert(&category);
+    }
+
+    /// Stop accepting an item type for new mints. Items already minted with it are kept
+    /// as they are. Only the contract owner can manage categories.
+    pub fn unregister_category(&mut self, category: String) {
+        self.assert_owner();
+        assert!(
+            self.categories.remove(&category),
+            "Category is not registered"
+        );
+    }
+
+    /// Let `minter_id` mint items of `item_types`, on top of any types it had already.
+    /// Only the contract owner can manage minters.
+    pub fn grant_mint_types(&mut self, minter_id: AccountId, item_types: Vec<String>) {
+        self.assert_owner();
+        let mut types = self
+            .minter_type_permissions
+            .get(&minter_id)
+            .unwrap_or_else(|| {
+                let mut prefix = b"minter".to_vec();
+                prefix.extend(env::sha256(minter_id.as_bytes()));
+                UnorderedSet::new(prefix)
+            });
+        for item_type in item_types {
+            types.insert(&item_type);
+        }
+        self.minter_type_permissions.insert(&minter_id, &types);
+    }
+
+    /// Withdraw `minter_id`'s permission to mint `item_types`. Only the contract owner can
+    /// manage minters.
+    pub fn revoke_mint_types(&mut self, minter_id: AccountId, item_types: Vec<String>) {
+        self.assert_owner();
+        if let Some(mut types) = self.minter_type_permissions.get(&minter_id) {
+            for item_type in item_types {
+                types.remove(&item_type);
+            }
+            self.minter_type_permissions.insert(&minter_id, &types);
+        }
+    }
+
+    pub fn get_mint_types(&self, minter_id: AccountId) -> Vec<String> {
+        self.minter_type_permissions
+            .get(&minter_id)
+            .map(|types| types.to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Get all registered categories.
+    pub fn get_categories(&self) -> Vec<String> {
+        self.categories.to_vec()
+    }
+
+    /// Cap how many items of `item_type` can ever be minted; `None` lifts the cap. A cap
+    /// below the number already minted just stops further mints. Split-off stacks and
+    /// bundles don't count as new supply. Only the contract owner can set caps.
+    pub fn set_type_cap(&mut self, item_type: String, cap: Option<u64>) {
+        self.assert_owner();
+        match cap {
+            Some(cap) => self.type_caps.insert(&item_type, &cap),
+            None => self.type_caps.remove(&item_type),
+        };
+    }
+
+    /// Get how many items of `item_type` were minted so far, and its cap if it has one.
+    pub fn type_supply(&self, item_type: String) -> (u64, Option<u64>) {
+        (
+            self.type_minted.get(&item_type).unwrap_or(0),
+            self.type_caps.get(&item_type),
+        )
+    }
+
+    /// Set the largest metadata, in bytes, accepted by mints and metadata updates. Existing
+    /// items are unaffected. Only the contract owner can set it.
+    pub fn set_max_metadata_bytes(&mut self, max_metadata_bytes: u32) {
+        self.assert_owner();
+        assert!(max_metadata_bytes > 0, "Metadata limit must be positive");
+        self.max_metadata_bytes = max_metadata_bytes;
+    }
+
+    pub fn get_max_metadata_bytes(&self) -> u32 {
+        self.max_metadata_bytes
+    }
+
+    /// Make these top-level metadata keys immutable on every item, replacing the previous
+    /// list; e.g. ["media"] or ["power", "weight"]. Reveals still replace placeholder
+    /// metadata wholesale. Only the contract owner can set it.
+    pub fn set_locked_fields(&mut self, fields: Vec<String>) {
+        self.assert_owner();
+        self.locked_fields.clear();
+        for field in fields {
+            self.locked_fields.insert(&field);
+        }
+    }
+
+    pub fn get_locked_fields(&self) -> Vec<String> {
+        self.locked_fields.to_vec()
+    }
+
+    /// Require the metadata `media` URL of new mints and metadata updates to start with one
+    /// of `schemes` (e.g. "https://", "ipfs://"); an empty list lifts the restriction.
+    /// Only the contract owner can set it.
+    pub fn set_allowed_media_schemes(&mut self, schemes: Vec<String>) {
+        self.assert_owner();
+        assert!(
+            schemes.iter().all(|scheme| !scheme.is_empty()),
+            "Media schemes can't be empty"
+        );
+        self.allowed_media_schemes = schemes;
+    }
+
+    pub fn get_allowed_media_schemes(&self) -> Vec<String> {
+        self.allowed_media_schemes.clone()
+    }
+
+    /// Set how many decimals UIs should use to display quantities of a registered item type,
+    /// e.g. 2 shows a "gold" stack of 1050 as 10.50. Only the contract owner can set it.
+    pub fn set_decimals(&mut self, item_type: String, decimals: u8) {
+        self.assert_owner();
+        self.assert_registered_category(&item_type);
+        assert!(
+            decimals <= MAX_DECIMALS,
+            "Decimals can't exceed {}",
+            MAX_DECIMALS
+        );
+        self.decimals.insert(&item_type, &decimals);
+    }
+
+    /// Get the display decimals of an item type; 0 unless configured.
+    pub fn get_decimals(&self, item_type: String) -> u8 {
+        self.decimals.get(&item_type).unwrap_or(0)
+    }
+
+    /// Set the royalty inherited by items minted without one. Only the contract owner can
+    /// set it.
+    pub fn set_default_royalty(&mut self, royalty: HashMap<AccountId, u16>) {
+        self.assert_owner();
+        assert_valid_royalty(&royalty);
+        self.default_royalty = royalty;
+    }
+
+    /// Share `bps` of every sale among the item's last `depth` previous holders in proportion
+    /// to how many items each of them currently holds, or turn it off with `None`. Holders
+    /// who have sold everything get nothing, and if none holds anything the seller keeps the
+    /// slice. Only the contract owner can set it.
+    pub fn set_holder_royalty(&mut self, holder_royalty: Option<HolderRoyalty>) {
+        self.assert_owner();
+        if let Some(holder_royalty) = &holder_royalty {
+            assert!(
+                holder_royalty.bps as u32 <= MAX_TOTAL_ROYALTY_BPS,
+                "Holder royalty of {} bps exceeds the cap of {} bps",
+                holder_royalty.bps,
+                MAX_TOTAL_ROYALTY_BPS
+            );
+            assert!(
+                holder_royalty.depth >= 1 && holder_royalty.depth <= MAX_HOLDER_ROYALTY_DEPTH,
+                "Holder royalty depth must be between 1 and {}",
+                MAX_HOLDER_ROYALTY_DEPTH
+            );
+        }
+        self.holder_royalty = holder_royalty;
+    }
+
+    pub fn get_holder_royalty(&self) -> Option<HolderRoyalty> {
+        self.holder_royalty.clone()
+    }
+
+    /// Get the royalty inherited by items minted without one.
+    pub fn get_default_royalty(&self) -> HashMap<AccountId, u16> {
+        self.default_royalty.clone()
+    }
+
+    /// Restrict `nft_transfer_call` to known receiver contracts, creating the allowlist if
+    /// there is none yet. Only the contract owner can manage the allowlist.
+    pub fn add_allowed_receiver(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        let mut allowlist = self
+            .receiver_allowlist
+            .take()
+            .unwrap_or_else(|| UnorderedSet::new(b"receiver_allowlist".to_vec()));
+        allowlist.insert(&account_id);
+        self.receiver_allowlist = Some(allowlist);
+    }
+
+    /// Remove a receiver contract from the allowlist. Only the contract owner can manage the
+    /// allowlist.
+    pub fn remove_allowed_receiver(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        let allowlist = self
+            .receiver_allowlist
+            .as_mut()
+            .expect("There is no receiver allowlist");
+        allowlist.remove(&account_id);
+    }
+
+    /// Drop the allowlist so that `nft_transfer_call` accepts any receiver again.
+    /// Only the contract owner can manage the allowlist.
+    pub fn clear_receiver_allowlist(&mut self) {
+        self.assert_owner();
+        if let Some(mut allowlist) = self.receiver_allowlist.take() {
+            allowlist.clear();
+        }
+    }
+
+    /// Get the receiver allowlist, or `None` if any receiver is accepted.
+    pub fn get_receiver_allowlist(&self) -> Option<Vec<AccountId>> {
+        self.receiver_allowlist.as_ref().map(|set| set.to_vec())
+    }
+
+    /// Limit how many times each item may change hands, or lift the limit with `None`.
+    /// Only the contract owner can set it.
+    pub fn set_max_transfers(&mut self, max_transfers: Option<u32>) {
+        self.assert_owner();
+        self.max_transfers = max_transfers;
+    }
+
+    /// Get how many more times an item may change hands, or `None` if unlimited.
+    pub fn transfers_remaining(&self, id: String) -> Option<u32> {
+        let item = self.items.get(&id).expect("Item does not exist");
+        self.max_transfers
+            .map(|max| max.saturating_sub(item.transfer_count))
+    }
+
+    /// Set the contract that gets an `on_item_transfer` call whenever an item changes hands,
+    /// or stop notifying with `None`. Only the contract owner can set it.
+    pub fn set_observer(&mut self, observer: Option<AccountId>) {
+        self.assert_owner();
+        self.observer = observer;
+    }
+
+    pub fn get_observer(&self) -> Option<AccountId> {
+        self.observer.clone()
+    }
+
+    /// Set the gas attached to `nft_on_transfer`, to the `nft_resolve_transfer` callback
+    /// and to observer notifications. Each must be between 1 and 100 Tgas.
+    /// Only the contract owner can set them.
+    pub fn set_cross_contract_gas(
+        &mut self,
+        gas_for_nft_on_transfer: Gas,
+        gas_for_resolve_transfer: Gas,
+        gas_for_on_item_transfer: Gas,
+    ) {
+        self.assert_owner();
+        assert_hook_gas("nft_on_transfer", gas_for_nft_on_transfer);
+        assert_hook_gas("nft_resolve_transfer", gas_for_resolve_transfer);
+        assert_hook_gas("on_item_transfer", gas_for_on_item_transfer);
+        self.gas_for_nft_on_transfer = gas_for_nft_on_transfer;
+        self.gas_for_resolve_transfer = gas_for_resolve_transfer;
+        self.gas_for_on_item_transfer = gas_for_on_item_transfer;
+    }
+
+    /// Get the gas for (`nft_on_transfer`, `nft_resolve_transfer`, `on_item_transfer`).
+    pub fn cross_contract_gas(&self) -> (Gas, Gas, Gas) {
+        (
+            self.gas_for_nft_on_transfer,
+            self.gas_for_resolve_transfer,
+            self.gas_for_on_item_transfer,
+        )
+    }
+
+    /// Make each account wait `duration_ns` between two uses of `action` ("transfer" or
+    /// "redeem"), or remove the cooldown with `None`. Only the contract owner can set it.
+    pub fn set_cooldown(&mut self, action: String, duration_ns: Option<u64>) {
+        self.assert_owner();
+        assert!(
+            [TRANSFER_ACTION, REDEEM_ACTION].contains(&action.as_str()),
+            "Unknown action {}",
+            action
+        );
+        match duration_ns {
+            Some(duration_ns) => self.cooldown_durations.insert(&action, &duration_ns),
+            None => self.cooldown_durations.remove(&action),
+        };
+    }
+
+    /// Get the block timestamp from which `account_id` may perform `action` again; 0 if it
+    /// isn't cooling down.
+    pub fn cooldown_until(&self, account_id: AccountId, action: String) -> u64 {
+        self.cooldowns.get(&(account_id, action)).unwrap_or(0)
+    }
+
+    /// Get the current transfer fee in yoctoNEAR.
+    pub fn get_transfer_fee(&self) -> U128 {
+        U128(self.transfer_fee)
+    }
+
+    /// Get the account receiving transfer fees.
+    pub fn get_treasury(&self) -> AccountId {
+        self.treasury_id.clone()
+    }
+
+    /// Get details of a specific item by ID.
+    pub fn get_item(&self, id: String) -> GameItem {
+        self.items
+            .get(&id)
+            .expect("Item does not exist")
+            .decompressed()
+    }
+
+    /// Get an item in NEP-171 form, or `None` if it doesn't exist.
+    pub fn nft_token(&self, token_id: String) -> Option<JsonToken> {
+        self.items.get(&token_id).map(JsonToken::from)
+    }
+
+    /// Split a sale of an item at `balance` the way `buy_item` would: royalty and
+    /// previous-holder cuts first, the rest to the current holder. Fails if the split would
+    /// pay more than `max_len_payout` accounts.
+    pub fn nft_payout(
+        &self,
+        token_id: String,
+        balance: U128,
+        max_len_payout: Option<u32>,
+    ) -> Payout {
+        let item = self.items.get(&token_id).expect("Item does not exist");
+        let cuts = self.internal_sale_payout(&item, balance.0);
+        let remainder = balance.0 - cuts.values().sum::<Balance>();
+        let mut payout: HashMap<AccountId, U128> = cuts
+            .into_iter()
+            .map(|(recipient, amount)| (recipient, U128(amount)))
+            .collect();
+        if remainder > 0 {
+            payout.entry(item.owner_id).or_insert(U128(0)).0 += remainder;
+        }
+        if let Some(max_len_payout) = max_len_payout {
+            assert!(
+                payout.len() <= max_len_payout as usize,
+                "Payout has {} recipients, more than the {} allowed",
+                payout.len(),
+                max_len_payout
+            );
+        }
+        Payout { payout }
+    }
+
+    /// Get who holds an item, who may transfer it and whether it is locked or soulbound,
+    /// or `None` if the item doesn't exist.
+    pub fn token_access(&self, id: String) -> Option<TokenAccess> {
+        self.items.get(&id).map(|item| TokenAccess {
+            owner_id: item.owner_id,
+            approved_account_ids: item.approved_account_ids,
+            locked: item.locked,
+            soulbound: item.soulbound,
+        })
+    }
+
+    /// Check whether `account_id` could transfer an item right now: it must hold the item or
+    /// be approved on it, the item (and anything attached to it) must be free to move, and
+    /// no transfer cooldown may be running. The receiver isn't known, so receiver checks
+    /// and the transfer fee are left out. `false` for an unknown item.
+    pub fn can_transfer(&self, account_id: AccountId, id: String) -> bool {
+        match self.items.get(&id) {
+            Some(item) => {
+                item.is_held_or_approved(&account_id)
+                    && transfer_blocker(&item).is_none()
+                    && self.within_transfer_limit(&item)
+                    && self.attached_transfer_blocker(&item).is_none()
+                    && !self.cooldown_active(&account_id, TRANSFER_ACTION)
+            }
+            None => false,
+        }
+    }
+
+    /// Check whether `account_id` could burn (redeem) an item right now. Only the holder
+    /// can, and not while the item is locked, bundled or attached. `false` for an unknown
+    /// item.
+    pub fn can_burn(&self, account_id: AccountId, id: String) -> bool {
+        match self.items.get(&id) {
+            Some(item) => {
+                item.owner_id == account_id
+                    && self.burn_blocker(&item).is_none()
+                    && !self.cooldown_active(&account_id, REDEEM_ACTION)
+            }
+            None => false,
+        }
+    }
+
+    /// Get only the metadata of an item, or `None` if it doesn't exist.
+    pub fn get_metadata(&self, id: String) -> Option<String> {
+        self.items.get(&id).map(|item| item.decompressed().metadata)
+    }
+
+    /// Check that an item's metadata still matches the hash recorded when it was written.
+    pub fn verify_metadata(&self, id: String) -> bool {
+        let item = self
+            .items
+            .get(&id)
+            .expect("Item does not exist")
+            .decompressed();
+        hash_metadata(&item.metadata) == item.content_hash
+    }
+
+    /// Get an item's provenance hash, or `None` if the item doesn't exist. Each tracked
+    /// transfer sets it to sha256(previous hash || from || to || block timestamp as 8
+    /// little-endian bytes), starting from 32 zero bytes, so the history rebuilt from
+    /// `transfer` events and their block timestamps can be checked against it.
+    pub fn provenance_hash(&self, id: String) -> Option<[u8; 32]> {
+        self.items.get(&id).map(|item| item.provenance_hash)
+    }
+
+    /// Turn provenance tracking on or off for future transfers. Turning it off leaves
+    /// existing hashes as they are. Only the contract owner can toggle it.
+    pub fn set_provenance_tracking(&mut self, enabled: bool) {
+        self.assert_owner();
+        self.track_provenance = enabled;
+    }
+
+    /// Tell apart minted items, lazily reserved ids and unknown ids.
+    pub fn get_item_status(&self, id: String) -> ItemStatus {
+        if self.items.contains_key(&id) {
+            ItemStatus::Minted
+        } else if self.lazy_reserved.contains_key(&id) {
+            ItemStatus::Reserved
+        } else {
+            ItemStatus::Unknown
+        }
+    }
+
+    /// Enumerate items ordered by id, starting at `from_id` itself (or the first id after it,
+    /// if it doesn't exist). `limit` is capped at 100.
+    pub fn nft_tokens(&self, from_id: Option<String>, limit: Option<u64>) -> Vec<GameItem> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_EXPORT_LIMIT) as usize;
+        let ids: Vec<String> = match from_id {
+            Some(from_id) => self
+                .item_ids
+                .range((Bound::Included(from_id), Bound::Unbounded))
+                .take(limit)
+                .map(|(id, _)| id)
+                .collect(),
+            None => self.item_ids.iter().take(limit).map(|(id, _)| id).collect(),
+        };
+        ids.iter()
+            .filter_map(|id| self.items.get(id))
+            .map(GameItem::decompressed)
+            .collect()
+    }
+
+    /// Enumerate items ordered by id, starting right after `after_id`. Paging with the last
+    /// id of the previous page never skips or repeats items, even if items were minted or
+    /// burned in between. `limit` is capped at 100.
+    pub fn nft_tokens_after(&self, after_id: Option<String>, limit: Option<u64>) -> Vec<GameItem> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_EXPORT_LIMIT) as usize;
+        let ids: Vec<String> = match after_id {
+            Some(after_id) => self
+                .item_ids
+                .iter_from(after_id)
+                .take(limit)
+                .map(|(id, _)| id)
+                .collect(),
+            None => self.item_ids.iter().take(limit).map(|(id, _)| id).collect(),
+        };
+        ids.iter()
+            .filter_map(|id| self.items.get(id))
+            .map(GameItem::decompressed)
+            .collect()
+    }
+
+    /// Browse items of one rarity tier, ordered by id, starting right after `after_id`.
+    /// Pass the last id of the previous page to continue. `limit` is capped at 100.
+    pub fn items_by_rarity(
+        &self,
+        rarity: Rarity,
+        after_id: Option<String>,
+        limit: Option<u64>,
+    ) -> Vec<GameItem> {
+        page_group(&self.rarity_index, rarity, after_id, limit)
+            .iter()
+            .filter_map(|id| self.items.get(id))
+            .map(GameItem::decompressed)
+            .collect()
+    }
+
+    /// Tally the owner's items by rarity, e.g. for guild leaderboards. Pages over the
+    /// owner's inventory ordered by id (at most 100 items per page), so large inventories
+    /// are summed page by page. Tiers the page holds none of are left out.
+    pub fn rarity_breakdown_for_owner(
+        &self,
+        owner_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> HashMap<Rarity, u64> {
+        let mut tally = HashMap::new();
+        let ids = paginate_stable(self.get_items_by_owner(owner_id), from_index, limit);
+        for item in ids.iter().filter_map(|id| self.items.get(id)) {
+            *tally.entry(item.rarity).or_insert(0) += 1;
+        }
+        tally
+    }
+
+    /// List metadata names shared by more than one item, with the ids sharing each name.
+    /// Scans up to `limit` (default 50, capped at 100) distinct names in order, skipping the
+    /// first `from_index`, so a page may hold few or no duplicates. Skipping reads every
+    /// name before `from_index`; use `find_duplicate_names_after` for large collections.
+    pub fn find_duplicate_names(
+        &self,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<(String, Vec<String>)> {
+        let from_index = from_index.map(|index| index.0).unwrap_or(0) as usize;
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_EXPORT_LIMIT) as usize;
+        let mut names: Vec<(String, Vec<String>)> = Vec::new();
+        let mut current: Option<String> = None;
+        let mut position = 0;
+        for ((name, id), _) in self.name_index.iter() {
+            if current.as_ref() != Some(&name) {
+                if current.is_some() {
+                    position += 1;
+                }
+                if position >= from_index + limit {
+                    break;
+                }
+                if position >= from_index {
+                    names.push((name.clone(), Vec::new()));
+                }
+                current = Some(name);
+            }
+            if let Some((_, ids)) = names.last_mut().filter(|_| position >= from_index) {
+                ids.push(id);
+            }
+        }
+        names.retain(|(_, ids)| ids.len() > 1);
+        names
+    }
+
+    /// Like `find_duplicate_names`, but scans up to `limit` (default 50, capped at 100)
+    /// named items ordered by (name, id), starting right after the `after` cursor, so the
+    /// ids of one name may continue on the next page. Pass the returned `next` back as
+    /// `after` until it is `None`.
+    pub fn find_duplicate_names_after(
+        &self,
+        after: Option<(String, String)>,
+        limit: Option<u64>,
+    ) -> DuplicateNamesPage {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_EXPORT_LIMIT) as usize;
+        let keys: Vec<(String, String)> = match after {
+            Some(after) => self
+                .name_index
+                .iter_from(after)
+                .take(limit)
+                .map(|(key, _)| key)
+                .collect(),
+            None => self
+                .name_index
+                .iter()
+                .take(limit)
+                .map(|(key, _)| key)
+                .collect(),
+        };
+
+        let mut duplicates: Vec<(String, Vec<String>)> = Vec::new();
+        for (index, (name, id)) in keys.iter().enumerate() {
+            // Neighbours outside the page are looked up so names spanning pages still count
+            let previous = match index {
+                0 => self.name_index.lower(&keys[0]),
+                _ => Some(keys[index - 1].clone()),
+            };
+            let next = match keys.get(index + 1) {
+                Some(key) => Some(key.clone()),
+                None => self.name_index.higher(&keys[index]),
+            };
+            let shares_name = |neighbour: &Option<(String, String)>| match neighbour {
+                Some((neighbour_name, _)) => neighbour_name == name,
+                None => false,
+            };
+            if !shares_name(&previous) && !shares_name(&next) {
+                continue;
+            }
+            match duplicates.last_mut() {
+                Some((last_name, ids)) if last_name == name => ids.push(id.clone()),
+                _ => duplicates.push((name.clone(), vec![id.clone()])),
+            }
+        }
+
+        let next = match keys.last() {
+            Some(last) if keys.len() == limit && self.name_index.higher(last).is_some() => {
+                Some(last.clone())
+            }
+            _ => None,
+        };
+        DuplicateNamesPage { duplicates, next }
+    }
+
+    /// Page through the items of one type, ordered by id, starting right after `after_id`.
+    /// Pass the last id of the previous page to continue. `limit` is capped at 100.
+    pub fn items_by_type(
+        &self,
+        item_type: String,
+        after_id: Option<String>,
+        limit: Option<u64>,
+    ) -> Vec<GameItem> {
+        page_group(&self.type_index, item_type, after_id, limit)
+            .iter()
+            .filter_map(|id| self.items.get(id))
+            .map(GameItem::decompressed)
+            .collect()
+    }
+
+    /// Get (total ever minted, currently existing, total burned). Splits and bundles count
+    /// as mints; merges, redemptions and unbundling count as burns.
+    pub fn supply_breakdown(&self) -> (u64, u64, u64) {
+        (self.total_minted, self.item_ids.len(), self.total_burned)
+    }
+
+    /// Scan a page of up to `limit` ids (default 50, capped at 100) of the global id index,
+    /// counting the ids that still have an item record and dropping the ones that don't.
+    /// `after_id` `None` starts a new recount; pass the returned `next` to continue. Once
+    /// `next` is `None`, call `finalize_recount`. Only the contract owner can recount.
+    pub fn recount_supply(
+        &mut self,
+        after_id: Option<String>,
+        limit: Option<u64>,
+    ) -> RecountProgress {
+        self.assert_owner();
+        match after_id {
+            None => {
+                self.recount_cursor = None;
+                self.recount_counted = 0;
+                self.recount_complete = false;
+                self.recount_started_at = (self.total_minted, self.total_burned);
+            }
+            Some(after_id) => assert!(
+                !self.recount_complete && self.recount_cursor.as_ref() == Some(&after_id),
+                "Recount must continue after {:?}",
+                self.recount_cursor
+            ),
+        }
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_EXPORT_LIMIT) as usize;
+        let page: Vec<String> = match &self.recount_cursor {
+            Some(cursor) => self
+                .item_ids
+                .iter_from(cursor.clone())
+                .take(limit)
+                .map(|(id, _)| id)
+                .collect(),
+            None => self.item_ids.iter().take(limit).map(|(id, _)| id).collect(),
+        };
+        for id in &page {
+            if self.items.contains_key(id) {
+                self.recount_counted += 1;
+            } else {
+                self.item_ids.remove(id);
+            }
+        }
+        if let Some(last) = page.last() {
+            self.recount_cursor = Some(last.clone());
+        }
+        self.recount_complete = match &self.recount_cursor {
+            Some(cursor) => self.item_ids.higher(cursor).is_none(),
+            None => true,
+        };
+        RecountProgress {
+            counted: self.recount_counted,
+            next: self
+                .recount_cursor
+                .clone()
+                .filter(|_| !self.recount_complete),
+        }
+    }
+
+    /// Apply a completed `recount_supply` pass: when `total_minted - total_burned` no longer
+    /// matches the counted supply, `total_minted` is reset to the counted supply plus
+    /// `total_burned` (burned items leave nothing behind to recount) and a
+    /// `supply_corrected` event is emitted. Returns whether a correction was applied. Items
+    /// minted or burned during the pass void it, and it has to be run again.
+    /// Only the contract owner can finalize.
+    pub fn finalize_recount(&mut self) -> bool {
+        self.assert_owner();
+        assert!(
+            self.recount_complete,
+            "Recount has not scanned every id yet"
+        );
+        assert_eq!(
+            self.recount_started_at,
+            (self.total_minted, self.total_burned),
+            "Items were minted or burned during the recount, start it again"
+        );
+        let counted = self.recount_counted;
+        self.recount_cursor = None;
+        self.recount_counted = 0;
+        self.recount_complete = false;
+        if self.total_minted.checked_sub(self.total_burned) == Some(counted) {
+            return false;
+        }
+        let old_total_minted = self.total_minted;
+        self.total_minted = counted + self.total_burned;
+        emit_event(Event::SupplyCorrected(SupplyCorrectedData {
+            old_total_minted,
+            total_minted: self.total_minted,
+            total_burned: self.total_burned,
+            total_supply: counted,
+        }));
+        true
+    }
+
+    /// Count the accounts currently holding at least one item.
+    pub fn unique_owners(&self) -> u64 {
+        self.owner_ids.len()
+    }
+
+    /// Export a page of full item records ordered by id, starting right after `after_id`,
+    /// for backups and analytics. Pass the last id of the previous page to continue.
+    /// `limit` is capped at 100 to keep each call gas-bounded.
+    pub fn export_items(&self, after_id: Option<String>, limit: Option<u64>) -> Vec<GameItem> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_EXPORT_LIMIT);
+        self.nft_tokens_after(after_id, Some(limit))
+    }
+
+    /// Export a page of (owner, item count) pairs ordered by account id, starting right
+    /// after `after_id`. Pass the last account of the previous page to continue.
+    /// `limit` is capped at 100 to keep each call gas-bounded.
+    pub fn export_owners(
+        &self,
+        after_id: Option<AccountId>,
+        limit: Option<u64>,
+    ) -> Vec<(AccountId, u64)> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_EXPORT_LIMIT) as usize;
+        let owners: Vec<AccountId> = match after_id {
+            Some(after_id) => self
+                .owner_ids
+                .iter_from(after_id)
+                .take(limit)
+                .map(|(owner_id, _)| owner_id)
+                .collect(),
+            None => self
+                .owner_ids
+                .iter()
+                .take(limit)
+                .map(|(owner_id, _)| owner_id)
+                .collect(),
+        };
+        owners
+            .into_iter()
+            .map(|owner_id| {
+                let count = self
+                    .owner_to_items
+                    .get(&owner_id)
+                    .map(|set| set.len())
+                    .unwrap_or(0);
+                (owner_id, count)
+            })
+            .collect()
+    }
+
+    /// Page through every current holder with their item count, for analytics. Accounts
+    /// leave the list as soon as they hold nothing. Same pages as `export_owners`.
+    pub fn owners_with_counts(
+        &self,
+        after_id: Option<AccountId>,
+        limit: Option<u64>,
+    ) -> Vec<(AccountId, u64)> {
+        self.export_owners(after_id, limit)
+    }
+
+    /// Get up to `limit` of the most recently minted items, newest first.
+    /// Items burned since they were minted are left out.
+    pub fn recent_items(&self, limit: u32) -> Vec<GameItem> {
+        self.recent_mints
+            .iter()
+            .rev()
+            .filter_map(|id| self.items.get(id))
+            .take(limit as usize)
+            .map(GameItem::decompressed)
+            .collect()
+    }
+
+    /// Page through the union of items held by `accounts`, ordered by account and then by
+    /// item id so that consecutive pages line up. `limit` is capped at 100.
+    pub fn items_for_owners(
+        &self,
+        accounts: Vec<AccountId>,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<String> {
+        let mut accounts = accounts;
+        accounts.sort();
+        accounts.dedup();
+
+        let held: Vec<(AccountId, String)> = accounts
+            .into_iter()
+            .flat_map(|account_id| {
+                self.get_items_by_owner(account_id.clone())
+                    .into_iter()
+                    .map(move |id| (account_id.clone(), id))
+            })
+            .collect();
+        paginate_stable(held, from_index, limit)
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect()
+    }
+
+    /// Compare two inventories side by side for trade UIs: returns (items only `a` holds,
+    /// items only `b` holds), each sorted by id and paged with the same `from_index`/`limit`.
+    /// Ownership is exclusive, so there is never a common part. `limit` is capped at 100.
+    pub fn inventory_diff(
+        &self,
+        a: AccountId,
+        b: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> (Vec<String>, Vec<String>) {
+        if a == b {
+            return (Vec::new(), Vec::new());
+        }
+        let page = |account_id: AccountId| {
+            paginate_stable(self.get_items_by_owner(account_id), from_index, limit)
+        };
+        (page(a), page(b))
+    }
+
+    /// Find a page of the owner's item ids containing `substring`, sorted by id. Matching is
+    /// case-sensitive and the substring must be at least 3 bytes long, since every call
+    /// scans the owner's whole inventory. `limit` is capped at 100.
+    pub fn search_items_by_owner(
+        &self,
+        owner_id: AccountId,
+        substring: String,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<String> {
+        assert!(
+            substring.len() >= MIN_SEARCH_LENGTH,
+            "Search needs at least {} characters",
+            MIN_SEARCH_LENGTH
+        );
+        let ids: Vec<String> = self
+            .get_items_by_owner(owner_id)
+            .into_iter()
+            .filter(|id| id.contains(&substring))
+            .collect();
+        paginate_stable(ids, from_index, limit)
+    }
+
+    /// Check in one call that `account_id` currently holds every item in `ids`, e.g. a
+    /// quest's required gear. True for an empty list.
+    pub fn owns_all(&self, account_id: AccountId, ids: Vec<String>) -> bool {
+        match self.owner_to_items.get(&account_id) {
+            Some(set) => ids.iter().all(|id| set.contains(id)),
+            None => ids.is_empty(),
+        }
+    }
+
+    /// Check in one call that `account_id` holds at least one item in `ids`.
+    pub fn owns_any(&self, account_id: AccountId, ids: Vec<String>) -> bool {
+        match self.owner_to_items.get(&account_id) {
+            Some(set) => ids.iter().any(|id| set.contains(id)),
+            None => false,
+        }
+    }
+
+    /// Get all item IDs owned by a specific account, sorted by id.
+    pub fn get_items_by_owner(&self, owner_id: AccountId) -> Vec<String> {
+        let mut ids = self
+            .owner_to_items
+            .get(&owner_id)
+            .map(|set| set.to_vec())
+            .unwrap_or_default();
+        ids.sort();
+        ids
+    }
+}
+
+impl GameItems {
+    fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the contract owner can call this method"
+        );
+    }
+
+    fn assert_registered_category(&self, item_type: &String) {
+        assert!(
+            self.categories.contains(item_type),
+            "Item type {} is not a registered category",
+            item_type
+        );
+    }
+
+    // Reject `action` while `account_id` is cooling down from its last use, then start a
+    // new cooldown if one is configured for the action
+    fn check_and_set_cooldown(&mut self, account_id: &AccountId, action: &str) {
+        let duration = match self.cooldown_durations.get(&action.to_string()) {
+            Some(duration) => duration,
+            None => return,
+        };
+        let key = (account_id.clone(), action.to_string());
+        let now = env::block_timestamp();
+        let until = self.cooldowns.get(&key).unwrap_or(0);
+        assert!(
+            now >= until,
+            "{} is on cooldown for {} until {}",
+            action,
+            account_id,
+            until
+        );
+        self.cooldowns.insert(&key, &(now + duration));
+    }
+
+    // Whether `account_id` must still wait before repeating `action`
+    fn cooldown_active(&self, account_id: &AccountId, action: &str) -> bool {
+        self.cooldown_durations.contains_key(&action.to_string())
+            && env::block_timestamp()
+                < self
+                    .cooldowns
+                    .get(&(account_id.clone(), action.to_string()))
+                    .unwrap_or(0)
+    }
+
+    fn may_mint(&self, account_id: &AccountId, item_type: &String) -> bool {
+        if account_id == &self.owner_id {
+            return true;
+        }
+        match self.minter_type_permissions.get(account_id) {
+            Some(types) => types.contains(item_type),
+            None => false,
+        }
+    }
+
+    // Count one more mint of `item_type` against its supply cap
+    fn internal_count_type_mint(&mut self, item_type: &String) {
+        let minted = self.type_minted.get(item_type).unwrap_or(0) + 1;
+        if let Some(cap) = self.type_caps.get(item_type) {
+            assert!(minted <= cap, "Type cap reached");
+        }
+        self.type_minted.insert(item_type, &minted);
+    }
+
+    // Release one unit of `item_type`'s supply cap, for an item that left the type
+    fn internal_uncount_type_mint(&mut self, item_type: &String) {
+        let minted = self.type_minted.get(item_type).unwrap_or(0);
+        self.type_minted
+            .insert(item_type, &minted.saturating_sub(1));
+    }
+
+    fn assert_can_receive(&self, account_id: &AccountId) {
+        assert!(
+            !self.receive_blocked.contains(account_id),
+            "Account {} is blocked from receiving items",
+            account_id
+        );
+    }
+
+    fn assert_minting_open(&self) {
+        assert!(!self.minting_locked, "Minting is permanently locked");
+    }
+
+    // Size limit and media scheme checks shared by every metadata write
+    fn assert_valid_metadata(&self, metadata: &str) {
+        assert!(
+            metadata.len() <= self.max_metadata_bytes as usize,
+            "Metadata is {} bytes, the limit is {} bytes",
+            metadata.len(),
+            self.max_metadata_bytes
+        );
+        if self.allowed_media_schemes.is_empty() {
+            return;
+        }
+        let parsed = near_sdk::serde_json::from_str::<near_sdk::serde_json::Value>(metadata);
+        // NEP-177 allows `"media": null`, which means no media just like a missing key
+        let media = parsed.ok().and_then(|value| value.get("media").cloned());
+        if let Some(media) = media.filter(|media| !media.is_null()) {
+            let media = media.as_str().expect("Metadata media must be a string");
+            assert!(
+                self.allowed_media_schemes
+                    .iter()
+                    .any(|scheme| media.starts_with(scheme.as_str())),
+                "Media URL {} doesn't use an allowed scheme",
+                media
+            );
+        }
+    }
+
+    // Royalty and previous-holder cuts of a sale at `price`, by recipient
+    fn internal_sale_payout(&self, item: &GameItem, price: Balance) -> HashMap<AccountId, Balance> {
+        let mut payout = HashMap::new();
+        for (recipient, bps) in item.royalty.iter() {
+            *payout.entry(recipient.clone()).or_insert(0) += price * *bps as u128 / 10_000;
+        }
+        if let Some(holder_royalty) = &self.holder_royalty {
+            let mut holders: Vec<(&AccountId, u128)> = Vec::new();
+            for holder in item
+                .transfer_log
+                .iter()
+                .rev()
+                .take(holder_royalty.depth as usize)
+            {
+                if holders.iter().all(|(seen, _)| *seen != holder) {
+                    let held = self.owner_to_items.get(holder).map_or(0, |set| set.len());
+                    holders.push((holder, held as u128));
+                }
+            }
+            let total_held: u128 = holders.iter().map(|(_, held)| held).sum();
+            let slice = price * holder_royalty.bps as u128 / 10_000;
+            for (holder, held) in holders {
+                if let Some(share) = (slice * held).checked_div(total_held) {
+                    *payout.entry(holder.clone()).or_insert(0) += share;
+                }
+            }
+        }
+        payout.retain(|_, amount| *amount > 0);
+        payout
+    }
+
+    // Move up to `limit` of `from`'s items that may change hands to `to`. Receiver checks
+    // apply to every item alike, so they fail the whole call up front instead.
+    fn internal_sweep(&mut self, from: &AccountId, to: &AccountId, limit: u32) -> u32 {
+        assert_ne!(from, to, "Cannot sweep an account into itself");
+        self.assert_can_receive(to);
+        let movable: Vec<GameItem> = match self.owner_to_items.get(from) {
+            Some(set) => set
+                .iter()
+                .filter_map(|id| self.items.get(&id))
+                .filter(|item| {
+                    transfer_blocker(item).is_none()
+                        && self.within_transfer_limit(item)
+                        && self.attached_transfer_blocker(item).is_none()
+                })
+                .take(limit as usize)
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let moved = movable.len() as u32;
+        for item in movable {
+            self.internal_transfer(item, to);
+        }
+        moved
+    }
+
+    fn internal_approve(
+        &mut self,
+        mut item: GameItem,
+        account_id: &AccountId,
+        msg: Option<&String>,
+    ) -> Option<Promise> {
+        assert!(
+            item.approved_account_ids.contains_key(account_id)
+                || item.approved_account_ids.len() < self.max_approvals_per_token as usize,
+            "Item {} already has the maximum of {} approvals; use nft_revoke to free one",
+            item.id,
+            self.max_approvals_per_token
+        );
+        let approval_id = item.next_approval_id;
+        item.next_approval_id += 1;
+        item.approved_account_ids
+            .insert(account_id.clone(), approval_id);
+        self.items.insert(&item.id, &item);
+
+        msg.map(|msg| {
+            ext_nft_approval_receiver::ext(account_id.clone()).nft_on_approve(
+                item.id,
+                item.owner_id,
+                approval_id,
+                msg.clone(),
+            )
+        })
+    }
+
+    fn internal_list(&mut self, id: String, price: U128) {
+        let seller_id = env::predecessor_account_id();
+        let item = self.items.get(&id).expect("Item does not exist");
+        assert_eq!(
+            item.owner_id, seller_id,
+            "Only the owner can list this item"
+        );
+        assert!(price.0 > 0, "Price must be positive");
+        assert!(
+            self.min_price == 0 || price.0 >= self.min_price,
+            "Price {} is below the minimum of {}",
+            price.0,
+            self.min_price
+        );
+        assert!(
+            self.max_price == 0 || price.0 <= self.max_price,
+            "Price {} is above the maximum of {}",
+            price.0,
+            self.max_price
+        );
+        let listing = Listing { seller_id, price };
+        self.listings.insert(&id, &listing);
+
+        emit_event(Event::Listing(ListingData {
+            token_id: id,
+            seller_id: listing.seller_id,
+            price,
+        }));
+    }
+
+    fn internal_stack_for_caller(&self, id: &String) -> GameItem {
+        let item = self.items.get(id).expect("Item does not exist");
+        assert_eq!(
+            item.owner_id,
+            env::predecessor_account_id(),
+            "Only the owner can split or merge this item"
+        );
+        assert!(
+            matches!(item.kind, ItemKind::Stackable { .. }),
+            "Only stackable items can be split or merged"
+        );
+        assert!(item.bundled_in.is_none(), "Item is bundled");
+        assert!(item.attached_to.is_none(), "Item is attached to a parent");
+        assert!(!self.has_children(id), "Detach the item's children first");
+        assert!(!item.locked, "Item is locked");
+        assert!(item.rental.is_none(), "Item is rented out");
+        assert!(
+            !self.bundle_members.contains_key(id),
+            "Bundles can't be split or merged"
+        );
+        item
+    }
+
+    // Take `amount` off the stack `item` into a new stack `new_id` with the same holder,
+    // type and metadata; the new stack stays soulbound or metadata-locked like its source
+    // and inherits its transfer history, so splitting never resets `max_transfers`
+    fn internal_split_off(&mut self, mut item: GameItem, new_id: String, amount: u64) {
+        self.assert_minting_open();
+        let quantity = item.quantity();
+        assert!(
+            amount > 0 && amount < quantity,
+            "Split amount must be between 1 and {}",
+            quantity - 1
+        );
+
+        item.kind = ItemKind::Stackable {
+            quantity: quantity - amount,
+        };
+        self.items.insert(&item.id, &item);
+        let compressed = item.compressed;
+        let item = item.decompressed();
+        let mut split_off = GameItem::new(
+            new_id,
+            item.owner_id,
+            item.item_type,
+            item.metadata,
+            ItemKind::Stackable { quantity: amount },
+        );
+        split_off.creator_id = item.creator_id;
+        split_off.royalty = item.royalty;
+        split_off.rarity = item.rarity;
+        split_off.soulbound = item.soulbound;
+        split_off.metadata_locked = item.metadata_locked;
+        split_off.transfer_count = item.transfer_count;
+        split_off.transfer_log = item.transfer_log;
+        if compressed {
+            split_off.compress_metadata();
+        }
+        self.internal_mint(split_off);
+    }
+
+    fn internal_set_locked(&mut self, id: String, locked: bool) {
+        let mut item = self.items.get(&id).expect("Item does not exist");
+        assert_eq!(
+            item.owner_id,
+            env::predecessor_account_id(),
+            "Only the owner can lock or unlock this item"
+        );
+        assert!(item.bundled_in.is_none(), "Item is bundled");
+        item.locked = locked;
+        self.items.insert(&id, &item);
+    }
+
+    fn internal_mint(&mut self, item: GameItem) {
+        assert!(!self.items.contains_key(&item.id), "Item ID already exists");
+        assert!(
+            !self.lazy_reserved.contains_key(&item.id),
+            "Item ID is reserved"
+        );
+        let storage_before = env::storage_usage();
+        let owner_id = item.owner_id.clone();
+        let id = item.id.clone();
+        // Written up front so the charge record pays for itself; the cost is filled in below
+        if self.storage_deposits.contains_key(&owner_id) {
+            self.storage_charges.insert(&id, &(owner_id.clone(), 0));
+        }
+
+        self.items.insert(&item.id, &item);
+        self.item_ids.insert(&item.id, &());
+        self.total_minted += 1;
+        self.internal_add_item_to_owner(&item.owner_id, &item.id);
+        self.internal_add_item_to_type(&item.item_type, &item.id);
+        self.internal_index_name(&item);
+        self.rarity_index
+            .insert(&(item.rarity, item.id.clone()), &());
+
+        self.recent_mints.push(item.id);
+        if self.recent_mints.len() > self.recent_mints_capacity as usize {
+            self.recent_mints.remove(0);
+        }
+        let bytes = env::storage_usage() - storage_before;
+        if let Some(cost) = self.internal_charge_storage(&owner_id, bytes) {
+            self.storage_charges.insert(&id, &(owner_id, cost));
+        }
+    }
+
+    // Pay for `bytes` of new storage out of the account's deposit, if it has one, and
+    // return what was charged
+    fn internal_charge_storage(&mut self, account_id: &AccountId, bytes: u64) -> Option<Balance> {
+        self.storage_deposits.get(account_id).map(|mut balance| {
+            let cost = bytes as Balance * env::storage_byte_cost();
+            assert!(
+                balance.available.0 >= cost,
+                "Storage balance of {} has {} available, {} is needed; top up with \
+                 storage_deposit",
+                account_id,
+                balance.available.0,
+                cost
+            );
+            balance.available.0 -= cost;
+            self.storage_deposits.insert(account_id, &balance);
+            cost
+        })
+    }
+
+    // Credit `bytes` of freed storage back to the deposit of the account that was charged
+    // `charged` for the item, if it still has one. The refund is capped at the charge and
+    // never pushes `available` past what the account actually deposited.
+    fn internal_refund_storage(&mut self, account_id: &AccountId, bytes: u64, charged: Balance) {
+        if let Some(mut balance) = self.storage_deposits.get(account_id) {
+            let refund = (bytes as Balance * env::storage_byte_cost())
+                .min(charged)
+                .min(balance.total.0 - balance.available.0);
+            balance.available.0 += refund;
+            self.storage_deposits.insert(account_id, &balance);
+        }
+    }
+
+    // Validate and write new metadata, keeping the name index in sync, and emit a
+    // `metadata_update` event; the caller stores the item
+    fn internal_replace_metadata(&mut self, item: &mut GameItem, metadata: String) {
+        assert!(!item.metadata_locked, "Item metadata is locked");
+        self.assert_valid_metadata(&metadata);
+        self.internal_unindex_name(item);
+        item.set_metadata(metadata);
+        self.internal_index_name(item);
+        emit_event(Event::MetadataUpdate(MetadataUpdateData {
+            token_ids: vec![item.id.clone()],
+        }));
+    }
+
+    // Diff the item's current metadata against `metadata` on every locked key; metadata
+    // that isn't a JSON object has no keys
+    fn assert_locked_fields_unchanged(&self, item: &GameItem, metadata: &str) {
+        if self.locked_fields.is_empty() {
+            return;
+        }
+        let fields = |metadata: &str| {
+            near_sdk::serde_json::from_str::<
+                near_sdk::serde_json::Map<String, near_sdk::serde_json::Value>,
+            >(metadata)
+            .unwrap_or_default()
+        };
+        let old = fields(&item.clone().decompressed().metadata);
+        let new = fields(metadata);
+        for field in self.locked_fields.iter() {
+            assert!(
+                old.get(&field) == new.get(&field),
+                "Metadata field {} is locked",
+                field
+            );
+        }
+    }
+
+    fn internal_index_name(&mut self, item: &GameItem) {
+        if let Some(name) = item.name() {
+            self.name_index.insert(&(name, item.id.clone()), &());
+        }
+    }
+
+    fn internal_unindex_name(&mut self, item: &GameItem) {
+        if let Some(name) = item.name() {
+            self.name_index.remove(&(name, item.id.clone()));
+        }
+    }
+
+    fn internal_burn(&mut self, item: GameItem) {
+        let storage_before = env::storage_usage();
+        let charge = self.storage_charges.remove(&item.id);
+        self.items.remove(&item.id);
+        self.listings.remove(&item.id);
+        self.internal_clear_offer(&item.id);
+        self.item_ids.remove(&item.id);
+        self.total_burned += 1;
+        self.internal_remove_item_from_owner(&item.owner_id, &item.id);
+        self.internal_remove_item_from_type(&item.item_type, &item.id);
+        self.internal_unindex_name(&item);
+        self.rarity_index.remove(&(item.rarity, item.id.clone()));
+        let freed = storage_before.saturating_sub(env::storage_usage());
+        if let Some((payer, charged)) = charge {
+            self.internal_refund_storage(&payer, freed, charged);
+        }
+    }
+
+    // Shared by `transfer` and `nft_transfer_call`: authorizes the transfer and moves the
+    // item. Returns the previous owner.
+    fn internal_transfer_by_holder(&mut self, id: &String, new_owner_id: &AccountId) -> AccountId {
+        let (item, fee) = self.internal_authorize_holder_transfer(id, new_owner_id);
+        self.internal_pay_transfer_fee(fee);
+        let sender = item.owner_id.clone();
+        self.internal_transfer(item, new_owner_id);
+        sender
+    }
+
+    // Checks that the caller holds the item and may send it to `new_owner_id`, and settles
+    // the transfer fee, which is returned for the caller to pay out or hold
+    fn internal_authorize_holder_transfer(
+        &mut self,
+        id: &String,
+        new_owner_id: &AccountId,
+    ) -> (GameItem, Balance) {
+        let sender = env::predecessor_account_id();
+        let item = self.items.get(id).expect("Item does not exist");
+        assert!(
+            item.is_held_or_approved(&sender),
+            "Only the owner or an approved account can transfer this item"
+        );
+        self.check_and_set_cooldown(&sender, TRANSFER_ACTION);
+        let fee = self.internal_settle_holder_transfer(new_owner_id);
+        (item, fee)
+    }
+
+    // Leave `item` pending for `new_owner_id` to accept while pull transfers are enabled,
+    // holding the caller's `fee` until then
+    fn internal_offer_transfer(&mut self, item: GameItem, new_owner_id: AccountId, fee: Balance) {
+        assert_transferable(&item, &new_owner_id);
+        self.assert_can_receive(&new_owner_id);
+        self.assert_within_transfer_limit(&item);
+        assert!(
+            !self.pending_offers.contains_key(&item.id),
+            "Item already has a pending transfer, cancel it first"
+        );
+        self.pending_transfers
+            .insert(&(new_owner_id.clone(), item.id.clone()), &item.owner_id);
+        self.pending_offers.insert(
+            &item.id,
+            &(new_owner_id, env::predecessor_account_id(), fee),
+        );
+    }
+
+    // Drop the pending offer of an item, if any, and refund the fee held with it
+    fn internal_clear_offer(&mut self, id: &String) {
+        if let Some((receiver, payer, fee)) = self.pending_offers.remove(id) {
+            self.pending_transfers.remove(&(receiver, id.clone()));
+            if fee > 0 {
+                Promise::new(payer).transfer(fee);
+            }
+        }
+    }
+
+    fn internal_pay_transfer_fee(&self, fee: Balance) {
+        if fee > 0 {
+            Promise::new(self.treasury_id.clone()).transfer(fee);
+        }
+    }
+
+    // Applies the receiver registration gate and takes the transfer fee out of the caller's
+    // deposit, refunding any excess. Returns the fee for the caller to pay out or hold.
+    fn internal_settle_holder_transfer(&mut self, new_owner_id: &AccountId) -> Balance {
+        let sender = env::predecessor_account_id();
+        assert!(
+            !self.require_registered_receiver || self.registered_accounts.contains(new_owner_id),
+            "Receiver {} is not registered",
+            new_owner_id
+        );
+
+        let fee = if sender == self.owner_id {
+            0
+        } else {
+            self.transfer_fee
+        };
+        let deposit = env::attached_deposit();
+        assert!(
+            deposit >= fee,
+            "Attached deposit {} is less than the transfer fee {}",
+            deposit,
+            fee
+        );
+
+        if deposit > fee {
+            Promise::new(sender).transfer(deposit - fee);
+        }
+        fee
+    }
+
+    fn internal_transfer(&mut self, mut item: GameItem, new_owner_id: &AccountId) {
+        assert_transferable(&item, new_owner_id);
+        self.assert_can_receive(new_owner_id);
+        self.assert_within_transfer_limit(&item);
+        if let Some(reason) = self.attached_transfer_blocker(&item) {
+            panic!("{}", reason);
+        }
+        item.transfer_count += 1;
+        self.internal_move(item, new_owner_id);
+    }
+
+    // Why the items attached to `item` keep it from changing hands, if anything does
+    fn attached_transfer_blocker(&self, item: &GameItem) -> Option<String> {
+        let descendants = self.internal_descendants(&item.id);
+        if descendants.len() > self.max_attached as usize {
+            return Some(format!(
+                "Item carries {} attached items, the limit is {}; \
+                 detach some with detach_child first",
+                descendants.len(),
+                self.max_attached
+            ));
+        }
+        descendants
+            .iter()
+            .find(|descendant| descendant.locked || descendant.soulbound)
+            .map(|descendant| format!("Attached item {} is locked or soulbound", descendant.id))
+    }
+
+    // Why the holder can't burn `item` right now, if anything stops it
+    fn burn_blocker(&self, item: &GameItem) -> Option<&'static str> {
+        if item.bundled_in.is_some() {
+            Some("Item is bundled")
+        } else if item.attached_to.is_some() {
+            Some("Item is attached to a parent")
+        } else if self.has_children(&item.id) {
+            Some("Detach the item's children first")
+        } else if item.locked {
+            Some("Item is locked")
+        } else if item.rental.is_some() {
+            Some("Item is rented out")
+        } else if self.bundle_members.contains_key(&item.id) {
+            Some("Unbundle the bundle before redeeming it")
+        } else {
+            None
+        }
+    }
+
+    fn within_transfer_limit(&self, item: &GameItem) -> bool {
+        !matches!(self.max_transfers, Some(max) if item.transfer_count >= max)
+    }
+
+    fn assert_within_transfer_limit(&self, item: &GameItem) {
+        if let Some(max) = self.max_transfers {
+            assert!(
+                item.transfer_count < max,
+                "Item has reached its limit of {} transfers",
+                max
+            );
+        }
+    }
+
+    // Reassign an item without any transferability checks. Every change of hands goes
+    // through here, so this is where the `transfer` event is emitted.
+    fn internal_move(&mut self, mut item: GameItem, new_owner_id: &AccountId) {
+        // An offer only stands while its sender still holds the item
+        self.internal_clear_offer(&item.id);
+        self.internal_remove_item_from_owner(&item.owner_id, &item.id);
+        self.internal_add_item_to_owner(new_owner_id, &item.id);
+
+        // The notification is detached: nothing waits on it, so a failing observer can't
+        // revert the transfer
+        if let Some(observer) = &self.observer {
+            ext_transfer_observer::ext(observer.clone())
+                .with_static_gas(self.gas_for_on_item_transfer)
+                .on_item_transfer(item.id.clone(), item.owner_id.clone(), new_owner_id.clone());
+        }
+
+        if self.track_provenance {
+            item.provenance_hash = next_provenance_hash(
+                &item.provenance_hash,
+                &item.owner_id,
+                new_owner_id,
+                env::block_timestamp(),
+            );
+        }
+        item.transfer_log.push(item.owner_id.clone());
+        if item.transfer_log.len() > MAX_HOLDER_ROYALTY_DEPTH as usize {
+            item.transfer_log.remove(0);
+        }
+
+        emit_event(Event::Transfer(TransferData {
+            old_owner_id: item.owner_id.clone(),
+            new_owner_id: new_owner_id.clone(),
+            token_ids: vec![item.id.clone()],
+        }));
+
+        // Update ownership
+        item.owner_id = new_owner_id.clone();
+        item.approved_account_ids.clear();
+        // A listing is only valid while its seller still holds the item
+        self.listings.remove(&item.id);
+        self.items.insert(&item.id, &item);
+
+        // Attached children follow their parent
+        for child_id in self.get_children(item.id) {
+            let child = self
+                .items
+                .get(&child_id)
+                .expect("Child item does not exist");
+            self.internal_move(child, new_owner_id);
+        }
+    }
+
+    fn has_children(&self, id: &String) -> bool {
+        self.item_children.contains_key(id)
+    }
+
+    // All items attached to `id`, directly or through other attached items
+    fn internal_descendants(&self, id: &str) -> Vec<GameItem> {
+        let mut descendants = Vec::new();
+        let mut pending = self.get_children(id.to_string());
+        while let Some(child_id) = pending.pop() {
+            pending.extend(self.get_children(child_id.clone()));
+            descendants.push(
+                self.items
+                    .get(&child_id)
+                    .expect("Child item does not exist"),
+            );
+        }
+        descendants
+    }
+
+    fn internal_add_item_to_type(&mut self, item_type: &str, id: &str) {
+        self.type_index
+            .insert(&(item_type.to_string(), id.to_string()), &());
+    }
+
+    fn internal_remove_item_from_type(&mut self, item_type: &str, id: &str) {
+        self.type_index
+            .remove(&(item_type.to_string(), id.to_string()));
+    }
+
+    fn internal_add_item_to_owner(&mut self, owner_id: &AccountId, id: &String) {
+        let mut items_set = self
+            .owner_to_items
+            .get(owner_id)
+            .unwrap_or_else(|| new_owner_set(owner_id));
+        items_set.insert(id);
+        if items_set.len() == 1 {
+            self.owner_ids.insert(owner_id, &());
+        }
+        self.owner_to_items.insert(owner_id, &items_set);
+    }
+
+    // Callers pass the owner recorded on the item itself, which stays authoritative: if the
+    // owner's index entry has gone missing, it is recreated instead of failing the call
+    fn internal_remove_item_from_owner(&mut self, owner_id: &AccountId, id: &String) {
+        let mut items_set = self.owner_to_items.get(owner_id).unwrap_or_else(|| {
+            env::log_str(&format!(
+                "Item index of {} was missing and has been recreated",
+                owner_id
+            ));
+            new_owner_set(owner_id)
+        });
+        items_set.remove(id);
+        if items_set.is_empty() {
+            self.owner_ids.remove(owner_id);
+        }
+        self.owner_to_items.insert(owner_id, &items_set);
+    }
+}
+
+// Required for NEAR's testing framework
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::mock::VmAction;
+    use near_sdk::serde_json::{json, Value};
+    use near_sdk::test_utils::{get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::{testing_env, AccountId, RuntimeFeesConfig, VMConfig};
+
+    struct TestAccounts {
+        owner: AccountId,
+        alice: AccountId,
+        bob: AccountId,
+    }
+
+    fn get_accounts() -> TestAccounts {
+        TestAccounts {
+            owner: "owner.near".parse().unwrap(),
+            alice: "alice.near".parse().unwrap(),
+            bob: "bob.near".parse().unwrap(),
+        }
+    }
+
+    // Deploy the contract with the categories the tests mint into; the context must have
+    // the owner as predecessor
+    fn new_contract(owner_id: AccountId, recent_mints_capacity: Option<u32>) -> GameItems {
+        let mut contract = GameItems::new(owner_id, recent_mints_capacity);
+        for category in ["weapon", "armor", "ammo", "bolt"] {
+            contract.register_category(category.to_string());
+        }
+        contract
+    }
+
+    fn get_context(predecessor: AccountId) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder.predecessor_account_id(predecessor);
+        builder
+    }
+
+    #[test]
+    fn test_mint_and_transfer() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+        let alice = accounts.alice.clone();
+        let bob = accounts.bob.clone();
+
+        // Initialize contract
+        let mut context = get_context(owner.clone()).build();
+        testing_env!(context);
+        let mut contract = new_contract(owner.clone(), None);
+
+        // Mint a new item
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{\"name\":\"Sword\",\"power\":10}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        let item = contract.get_item("item1".to_string());
+        assert_eq!(item.owner_id, owner.clone());
+        assert_eq!(item.id, "item1");
+        assert_eq!(item.metadata, "{\"name\":\"Sword\",\"power\":10}");
+
+        // Transfer the item to Alice
+        context = get_context(owner.clone()).build();
+        testing_env!(context);
+        contract.transfer("item1".to_string(), alice.clone());
+        let item = contract.get_item("item1".to_string());
+        assert_eq!(item.owner_id, alice.clone());
+
+        // Attempt unauthorized transfer by Bob
+        context = get_context(bob.clone()).build();
+        testing_env!(context);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("item1".to_string(), bob.clone());
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_reserve_and_claim_lazy() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+        let alice = accounts.alice.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+
+        contract.reserve_lazy(
+            vec!["lazy1".to_string(), "lazy2".to_string()],
+            "armor".to_string(),
+            "{\"name\":\"Shield {id}\"}".to_string(),
+        );
+        assert_eq!(
+            contract.get_item_status("lazy1".to_string()),
+            ItemStatus::Reserved
+        );
+        assert_eq!(
+            contract.get_item_status("lazy2".to_string()),
+            ItemStatus::Reserved
+        );
+        assert!(contract.get_items_by_owner(alice.clone()).is_empty());
+
+        // Claiming materializes the full record for the receiver
+        let item = contract.claim_lazy("lazy1".to_string(), alice.clone());
+        assert_eq!(item.owner_id, alice.clone());
+        assert_eq!(item.metadata, "{\"name\":\"Shield lazy1\"}");
+        assert_eq!(
+            contract.get_item_status("lazy1".to_string()),
+            ItemStatus::Minted
+        );
+        assert_eq!(
+            contract.get_item("lazy1".to_string()).owner_id,
+            alice.clone()
+        );
+        assert_eq!(
+            contract.get_items_by_owner(alice.clone()),
+            vec!["lazy1".to_string()]
+        );
+
+        // A claimed id can't be claimed again
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.claim_lazy("lazy1".to_string(), alice.clone());
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_unclaimed_lazy_item_is_reported_distinctly() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.reserve_lazy(
+            vec!["lazy1".to_string()],
+            "armor".to_string(),
+            "{}".to_string(),
+        );
+
+        assert_eq!(
+            contract.get_item_status("item1".to_string()),
+            ItemStatus::Minted
+        );
+        assert_eq!(
+            contract.get_item_status("lazy1".to_string()),
+            ItemStatus::Reserved
+        );
+        assert_eq!(
+            contract.get_item_status("nope".to_string()),
+            ItemStatus::Unknown
+        );
+
+        // Reserved ids can't be minted directly
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.mint(
+                "lazy1".to_string(),
+                "weapon".to_string(),
+                "{}".to_string(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }));
+        assert!(result.is_err());
+    }
+
+    fn setup_fee_contract() -> (GameItems, TestAccounts) {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        contract.set_treasury("treasury.near".parse().unwrap());
+        contract.set_transfer_fee(U128(100));
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        (contract, accounts)
+    }
+
+    fn transfer_amounts_to(receiver: &str) -> Vec<Balance> {
+        get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id.as_str() == receiver)
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                VmAction::Transfer { deposit } => Some(deposit),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_paid_transfer_forwards_fee_to_treasury() {
+        let (mut contract, accounts) = setup_fee_contract();
+
+        let mut context = get_context(accounts.alice.clone());
+        context.attached_deposit(100);
+        testing_env!(context.build());
+        contract.transfer("item1".to_string(), accounts.bob.clone());
+
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.bob
+        );
+        assert_eq!(transfer_amounts_to("treasury.near"), vec![100]);
+        assert!(transfer_amounts_to(accounts.alice.as_str()).is_empty());
+    }
+
+    #[test]
+    fn test_underpaid_transfer_is_rejected() {
+        let (mut contract, accounts) = setup_fee_contract();
+
+        let mut context = get_context(accounts.alice.clone());
+        context.attached_deposit(99);
+        testing_env!(context.build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("item1".to_string(), accounts.bob.clone());
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.alice
+        );
+    }
+
+    #[test]
+    fn test_transfer_refunds_excess_deposit() {
+        let (mut contract, accounts) = setup_fee_contract();
+
+        let mut context = get_context(accounts.alice.clone());
+        context.attached_deposit(250);
+        testing_env!(context.build());
+        contract.transfer("item1".to_string(), accounts.bob.clone());
+
+        assert_eq!(transfer_amounts_to("treasury.near"), vec![100]);
+        assert_eq!(transfer_amounts_to(accounts.alice.as_str()), vec![150]);
+    }
+
+    #[test]
+    fn test_owner_and_force_transfers_are_fee_exempt() {
+        let (mut contract, accounts) = setup_fee_contract();
+
+        // The contract owner transfers without attaching the fee
+        testing_env!(get_context(accounts.owner.clone()).build());
+        contract.mint(
+            "item2".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.transfer("item2".to_string(), accounts.bob.clone());
+        assert_eq!(
+            contract.get_item("item2".to_string()).owner_id,
+            accounts.bob
+        );
+
+        // Admin force-transfers don't charge the item holder either
+        contract.force_transfer("item1".to_string(), accounts.bob.clone());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.bob
+        );
+        assert!(transfer_amounts_to("treasury.near").is_empty());
+    }
+
+    #[test]
+    fn test_bundle_and_unbundle() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+        let alice = accounts.alice.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        contract.mint(
+            "sword".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.mint(
+            "shield".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let bundle_id = contract.bundle(
+            vec!["sword".to_string(), "shield".to_string()],
+            "set".to_string(),
+            "{\"name\":\"Knight set\"}".to_string(),
+        );
+        assert_eq!(bundle_id, "set");
+        assert_eq!(contract.get_item("set".to_string()).owner_id, owner.clone());
+        assert_eq!(
+            contract.get_item("sword".to_string()).bundled_in,
+            Some("set".to_string())
+        );
+
+        // Sell the bundle, then the buyer unwraps it
+        contract.transfer("set".to_string(), alice.clone());
+        testing_env!(get_context(alice.clone()).build());
+        let released = contract.unbundle("set".to_string());
+        assert_eq!(released, vec!["sword".to_string(), "shield".to_string()]);
+        assert_eq!(
+            contract.get_item_status("set".to_string()),
+            ItemStatus::Unknown
+        );
+
+        for id in ["sword", "shield"] {
+            let item = contract.get_item(id.to_string());
+            assert_eq!(item.owner_id, alice.clone());
+            assert_eq!(item.bundled_in, None);
+        }
+        let mut alice_items = contract.get_items_by_owner(alice.clone());
+        alice_items.sort();
+        assert_eq!(alice_items, vec!["shield".to_string(), "sword".to_string()]);
+        assert!(contract.get_items_by_owner(owner.clone()).is_empty());
+
+        // Released items transfer individually again
+        contract.transfer("sword".to_string(), owner.clone());
+        assert_eq!(contract.get_item("sword".to_string()).owner_id, owner);
+    }
+
+    #[test]
+    fn test_bundled_item_cannot_be_transferred() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        contract.mint(
+            "sword".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.bundle(
+            vec!["sword".to_string()],
+            "set".to_string(),
+            "{}".to_string(),
+        );
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("sword".to_string(), accounts.alice.clone());
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.get_item("sword".to_string()).owner_id, owner);
+    }
+
+    #[test]
+    fn test_bundle_members_must_be_free_to_move() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["sword", "shield", "bow", "axe"] {
+            mint_item(&mut contract, id);
+        }
+        contract.lock_item("sword".to_string());
+        contract.set_soulbound("shield".to_string(), true);
+        contract.rent_out("bow".to_string(), accounts.bob.clone(), 1_000);
+        for id in ["sword", "shield", "bow"] {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.bundle(
+                    vec!["axe".to_string(), id.to_string()],
+                    "set".to_string(),
+                    "{}".to_string(),
+                );
+            }));
+            assert!(result.is_err(), "bundling {} should be rejected", id);
+        }
+        assert!(contract.get_item("axe".to_string()).bundled_in.is_none());
+
+        // Once sold, the bundler can no longer lock or rent out a member
+        contract.bundle(vec!["axe".to_string()], "set".to_string(), "{}".to_string());
+        contract.transfer("set".to_string(), accounts.alice.clone());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.lock_item("axe".to_string());
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.rent_out("axe".to_string(), accounts.bob.clone(), 1_000);
+        }));
+        assert!(result.is_err());
+
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.unbundle("set".to_string());
+        assert_eq!(
+            contract.get_item("axe".to_string()).owner_id,
+            accounts.alice
+        );
+    }
+
+    #[test]
+    fn test_bundle_cannot_be_attached_as_child() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        for id in ["rifle", "scope"] {
+            mint_item(&mut contract, id);
+        }
+        contract.bundle(
+            vec!["scope".to_string()],
+            "kit".to_string(),
+            "{}".to_string(),
+        );
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.attach_child("rifle".to_string(), "kit".to_string())
+        }));
+        assert!(result.is_err());
+
+        // A bundle left attached can't be unwrapped out from under its parent
+        let mut kit = contract.items.get(&"kit".to_string()).unwrap();
+        kit.attached_to = Some("rifle".to_string());
+        contract.items.insert(&"kit".to_string(), &kit);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.unbundle("kit".to_string())
+        }));
+        assert!(result.is_err());
+        assert!(contract.items.get(&"kit".to_string()).is_some());
+    }
+
+    #[test]
+    fn test_bundle_cannot_be_attached_as_parent() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        for id in ["rifle", "scope"] {
+            mint_item(&mut contract, id);
+        }
+        contract.bundle(
+            vec!["rifle".to_string()],
+            "kit".to_string(),
+            "{}".to_string(),
+        );
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.attach_child("kit".to_string(), "scope".to_string())
+        }));
+        assert!(result.is_err());
+        assert!(contract.get_children("kit".to_string()).is_empty());
+
+        contract.unbundle("kit".to_string());
+        contract.transfer("scope".to_string(), accounts.alice.clone());
+        assert_eq!(
+            contract.get_item("scope".to_string()).owner_id,
+            accounts.alice
+        );
+    }
+
+    #[test]
+    fn test_rented_or_soulbound_bundle_cannot_be_unbundled() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        for id in ["sword", "shield"] {
+            mint_item(&mut contract, id);
+        }
+        contract.bundle(
+            vec!["sword".to_string()],
+            "kit1".to_string(),
+            "{}".to_string(),
+        );
+        contract.bundle(
+            vec!["shield".to_string()],
+            "kit2".to_string(),
+            "{}".to_string(),
+        );
+        contract.rent_out("kit1".to_string(), accounts.alice.clone(), 1_000);
+        contract.set_soulbound("kit2".to_string(), true);
+
+        for id in ["kit1", "kit2"] {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.unbundle(id.to_string())
+            }));
+            assert!(result.is_err(), "unbundling {} should be rejected", id);
+            assert!(contract.items.get(&id.to_string()).is_some());
+        }
+        assert!(contract.get_rental("kit1".to_string()).is_some());
+    }
+
+    #[test]
+    fn test_recent_items_keeps_newest_first() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, Some(3));
+        for i in 1..=5 {
+            contract.mint(
+                format!("item{}", i),
+                "weapon".to_string(),
+                "{}".to_string(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+
+        let ids: Vec<String> = contract
+            .recent_items(10)
+            .into_iter()
+            .map(|item| item.id)
+            .collect();
+        assert_eq!(ids, vec!["item5", "item4", "item3"]);
+
+        let ids: Vec<String> = contract
+            .recent_items(2)
+            .into_iter()
+            .map(|item| item.id)
+            .collect();
+        assert_eq!(ids, vec!["item5", "item4"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Recent mints capacity can't exceed 100")]
+    fn test_recent_mints_capacity_is_bounded() {
+        let owner = get_accounts().owner;
+        testing_env!(get_context(owner.clone()).build());
+        new_contract(owner, Some(101));
+    }
+
+    #[test]
+    fn test_transfer_to_self_is_rejected() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("item1".to_string(), owner.clone());
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_items_by_owner(owner.clone()),
+            vec!["item1".to_string()]
+        );
+
+        // A transfer to a distinct account still goes through
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.alice
+        );
+    }
+
+    #[test]
+    fn test_verify_metadata_after_mint_and_update() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{\"power\":10}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert!(contract.verify_metadata("item1".to_string()));
+
+        contract.update_metadata("item1".to_string(), "{\"power\":11}".to_string());
+        let item = contract.get_item("item1".to_string());
+        assert_eq!(item.metadata, "{\"power\":11}");
+        assert_eq!(item.content_hash, hash_metadata("{\"power\":11}"));
+        assert!(contract.verify_metadata("item1".to_string()));
+    }
+
+    #[test]
+    fn test_verify_metadata_detects_drift() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{\"power\":10}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        // Write metadata behind the contract's back, leaving the stored hash stale
+        let mut item = contract.items.get(&"item1".to_string()).unwrap();
+        item.metadata = "{\"power\":9000}".to_string();
+        contract.items.insert(&"item1".to_string(), &item);
+
+        assert!(!contract.verify_metadata("item1".to_string()));
+    }
+
+    fn ids_of(items: Vec<GameItem>) -> Vec<String> {
+        items.into_iter().map(|item| item.id).collect()
+    }
+
+    #[test]
+    fn test_nft_tokens_are_ordered_by_id() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        for id in ["c", "a", "d", "b"] {
+            contract.mint(
+                id.to_string(),
+                "weapon".to_string(),
+                "{}".to_string(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+
+        assert_eq!(
+            ids_of(contract.nft_tokens(None, None)),
+            vec!["a", "b", "c", "d"]
+        );
+        assert_eq!(
+            ids_of(contract.nft_tokens(Some("b".to_string()), Some(2))),
+            vec!["b", "c"]
+        );
+        assert_eq!(
+            ids_of(contract.nft_tokens(Some("bb".to_string()), None)),
+            vec!["c", "d"]
+        );
+    }
+
+    #[test]
+    fn test_paging_is_stable_across_mints_and_burns() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        for id in ["b", "d", "f", "h"] {
+            contract.mint(
+                id.to_string(),
+                "weapon".to_string(),
+                "{}".to_string(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+
+        let first_page = ids_of(contract.nft_tokens_after(None, Some(2)));
+        assert_eq!(first_page, vec!["b", "d"]);
+
+        // Items appear and disappear on both sides of the cursor between calls
+        contract.mint(
+            "a".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.mint(
+            "e".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        let burned = contract.get_item("b".to_string());
+        contract.internal_burn(burned);
+
+        let mut seen = first_page;
+        let mut cursor = seen.last().cloned();
+        loop {
+            let page = ids_of(contract.nft_tokens_after(cursor, Some(2)));
+            if page.is_empty() {
+                break;
+            }
+            cursor = page.last().cloned();
+            seen.extend(page);
+        }
+        assert_eq!(seen, vec!["b", "d", "e", "f", "h"]);
+    }
+
+    #[test]
+    fn test_get_metadata() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{\"name\":\"Bow\"}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        assert_eq!(
+            contract.get_metadata("item1".to_string()),
+            Some("{\"name\":\"Bow\"}".to_string())
+        );
+        assert_eq!(contract.get_metadata("missing".to_string()), None);
+    }
+
+    #[test]
+    fn test_split_and_merge_stacks() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        contract.mint(
+            "arrows".to_string(),
+            "ammo".to_string(),
+            "{\"name\":\"Arrow\"}".to_string(),
+            Some(50),
+            None,
+            None,
+            None,
+        );
+
+        contract.split("arrows".to_string(), "arrows2".to_string(), 20);
+        assert_eq!(contract.get_item("arrows".to_string()).quantity(), 30);
+        let split_off = contract.get_item("arrows2".to_string());
+        assert_eq!(split_off.quantity(), 20);
+        assert_eq!(split_off.owner_id, owner);
+        assert_eq!(split_off.item_type, "ammo");
+        assert_eq!(split_off.metadata, "{\"name\":\"Arrow\"}");
+
+        contract.merge("arrows".to_string(), "arrows2".to_string());
+        assert_eq!(contract.get_item("arrows".to_string()).quantity(), 50);
+        assert_eq!(
+            contract.get_item_status("arrows2".to_string()),
+            ItemStatus::Unknown
+        );
+        assert_eq!(
+            contract.get_items_by_owner(owner),
+            vec!["arrows".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_split_and_merge_boundaries() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.mint(
+            "arrows".to_string(),
+            "ammo".to_string(),
+            "{}".to_string(),
+            Some(50),
+            None,
+            None,
+            None,
+        );
+        contract.mint(
+            "bolts".to_string(),
+            "bolt".to_string(),
+            "{}".to_string(),
+            Some(5),
+            None,
+            None,
+            None,
+        );
+
+        for amount in [0, 50, 51] {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.split("arrows".to_string(), "arrows2".to_string(), amount);
+            }));
+            assert!(result.is_err(), "split of {} should be rejected", amount);
+        }
+        assert_eq!(contract.get_item("arrows".to_string()).quantity(), 50);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.merge("arrows".to_string(), "bolts".to_string());
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.get_item("bolts".to_string()).quantity(), 5);
+    }
+
+    #[test]
+    fn test_registered_receiver_gate() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+        let alice = accounts.alice.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_require_registered_receiver(true);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("item1".to_string(), alice.clone());
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.get_item("item1".to_string()).owner_id, owner);
+
+        testing_env!(get_context(alice.clone()).build());
+        contract.register_account();
+        assert!(contract.is_registered(alice.clone()));
+
+        testing_env!(get_context(owner.clone()).build());
+        contract.transfer("item1".to_string(), alice.clone());
+        assert_eq!(contract.get_item("item1".to_string()).owner_id, alice);
+    }
+
+    #[test]
+    fn test_unregistered_receiver_allowed_by_default() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        assert!(!contract.is_registered(accounts.bob.clone()));
+        contract.transfer("item1".to_string(), accounts.bob.clone());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.bob
+        );
+    }
+
+    #[test]
+    fn test_reassign_owner_items_in_batches() {
+        let owner = get_accounts().owner;
+        let treasury: AccountId = "treasury.near".parse().unwrap();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for i in 1..=5 {
+            contract.mint(
+                format!("item{}", i),
+                "weapon".to_string(),
+                "{}".to_string(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+
+        assert_eq!(contract.reassign_owner_items(treasury.clone(), 2), 2);
+        assert_eq!(contract.get_items_by_owner(owner.clone()).len(), 3);
+        assert_eq!(contract.get_items_by_owner(treasury.clone()).len(), 2);
+
+        while contract.reassign_owner_items(treasury.clone(), 2) > 0 {}
+        assert!(contract.get_items_by_owner(owner).is_empty());
+        let mut moved = contract.get_items_by_owner(treasury.clone());
+        moved.sort();
+        assert_eq!(moved, vec!["item1", "item2", "item3", "item4", "item5"]);
+        for id in moved {
+            assert_eq!(contract.get_item(id).owner_id, treasury);
+        }
+    }
+
+    #[test]
+    fn test_reassign_owner_items_skips_locked() {
+        let owner = get_accounts().owner;
+        let treasury: AccountId = "treasury.near".parse().unwrap();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["item1", "item2", "item3"] {
+            contract.mint(
+                id.to_string(),
+                "weapon".to_string(),
+                "{}".to_string(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+        contract.lock_item("item2".to_string());
+
+        assert_eq!(contract.reassign_owner_items(treasury.clone(), 10), 2);
+        assert_eq!(contract.reassign_owner_items(treasury.clone(), 10), 0);
+        assert_eq!(contract.get_items_by_owner(owner.clone()), vec!["item2"]);
+        assert_eq!(contract.get_item("item2".to_string()).owner_id, owner);
+        assert_eq!(contract.get_items_by_owner(treasury).len(), 2);
+    }
+
+    #[test]
+    fn test_locked_item_cannot_be_transferred() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.lock_item("item1".to_string());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("item1".to_string(), accounts.alice.clone());
+        }));
+        assert!(result.is_err());
+
+        contract.unlock_item("item1".to_string());
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.alice
+        );
+    }
+
+    #[test]
+    fn test_mint_inherits_default_royalty() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        let default_royalty = HashMap::from([(owner.clone(), 500)]);
+        contract.set_default_royalty(default_royalty.clone());
+
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(
+            contract.get_item("item1".to_string()).royalty,
+            default_royalty
+        );
+
+        // An explicit royalty replaces the default entirely
+        let explicit = HashMap::from([(accounts.alice.clone(), 250)]);
+        contract.mint(
+            "item2".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            Some(explicit.clone()),
+            None,
+            None,
+        );
+        assert_eq!(contract.get_item("item2".to_string()).royalty, explicit);
+    }
+
+    #[test]
+    fn test_over_cap_default_royalty_is_rejected() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.set_default_royalty(HashMap::from([
+                (owner.clone(), 3_000),
+                (accounts.alice.clone(), 2_001),
+            ]));
+        }));
+        assert!(result.is_err());
+        assert!(contract.get_default_royalty().is_empty());
+    }
+
+    #[test]
+    fn test_items_for_owners_pages_the_union() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+        let alice = accounts.alice.clone();
+        let bob = accounts.bob.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        for id in ["b2", "a1", "b1", "a2", "a3"] {
+            contract.mint(
+                id.to_string(),
+                "weapon".to_string(),
+                "{}".to_string(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+        for id in ["a1", "a2", "a3"] {
+            contract.transfer(id.to_string(), alice.clone());
+        }
+        for id in ["b1", "b2"] {
+            contract.transfer(id.to_string(), bob.clone());
+        }
+
+        let members = vec![bob.clone(), alice.clone()];
+        assert_eq!(
+            contract.items_for_owners(members.clone(), None, None),
+            vec!["a1", "a2", "a3", "b1", "b2"]
+        );
+        assert_eq!(
+            contract.items_for_owners(members.clone(), Some(U128(0)), Some(2)),
+            vec!["a1", "a2"]
+        );
+        assert_eq!(
+            contract.items_for_owners(members.clone(), Some(U128(2)), Some(2)),
+            vec!["a3", "b1"]
+        );
+        assert_eq!(
+            contract.items_for_owners(members, Some(U128(4)), Some(2)),
+            vec!["b2"]
+        );
+    }
+
+    fn large_metadata() -> String {
+        let description = "A blade forged in dragonfire, etched with runes. ".repeat(50);
+        format!("{{\"name\":\"Sword\",\"description\":\"{}\"}}", description)
+    }
+
+    #[test]
+    fn test_compressed_metadata_round_trips() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.set_max_metadata_bytes(4096);
+        let metadata = large_metadata();
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            metadata.clone(),
+            None,
+            None,
+            Some(true),
+            None,
+        );
+
+        let stored = contract.items.get(&"item1".to_string()).unwrap();
+        assert!(stored.compressed);
+        assert!(stored.metadata.is_empty());
+
+        let item = contract.get_item("item1".to_string());
+        assert!(item.compressed);
+        assert_eq!(item.metadata, metadata);
+        assert_eq!(item.compressed_metadata, None);
+        assert_eq!(contract.get_metadata("item1".to_string()), Some(metadata));
+        assert!(contract.verify_metadata("item1".to_string()));
+
+        // Updates stay compressed
+        let updated = format!("{}!", large_metadata());
+        contract.update_metadata("item1".to_string(), updated.clone());
+        assert!(contract.items.get(&"item1".to_string()).unwrap().compressed);
+        assert_eq!(contract.get_metadata("item1".to_string()), Some(updated));
+    }
+
+    #[test]
+    fn test_compressed_metadata_saves_storage() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.set_max_metadata_bytes(4096);
+
+        let before = env::storage_usage();
+        contract.mint(
+            "plain".to_string(),
+            "weapon".to_string(),
+            large_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        let plain_bytes = env::storage_usage() - before;
+
+        let before = env::storage_usage();
+        contract.mint(
+            "packed".to_string(),
+            "weapon".to_string(),
+            large_metadata(),
+            None,
+            None,
+            Some(true),
+            None,
+        );
+        let packed_bytes = env::storage_usage() - before;
+
+        assert!(
+            packed_bytes * 2 < plain_bytes,
+            "compressed {} bytes vs plain {} bytes",
+            packed_bytes,
+            plain_bytes
+        );
+    }
+
+    fn function_calls_to(receiver: &str) -> Vec<(String, Gas)> {
+        get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id.as_str() == receiver)
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                VmAction::FunctionCall {
+                    function_name, gas, ..
+                } => Some((function_name, gas)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn setup_transfer_call_contract() -> (GameItems, TestAccounts) {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        (contract, accounts)
+    }
+
+    #[test]
+    fn test_transfer_call_to_allowed_receiver() {
+        let (mut contract, accounts) = setup_transfer_call_contract();
+        contract.add_allowed_receiver("market.near".parse().unwrap());
+
+        contract.nft_transfer_call(
+            "market.near".parse().unwrap(),
+            "item1".to_string(),
+            "list".to_string(),
+        );
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id.as_str(),
+            "market.near"
+        );
+        let calls = function_calls_to("market.near");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].0, "nft_on_transfer");
+        assert!(function_calls_to(env::current_account_id().as_str())
+            .iter()
+            .any(|(name, _)| name == "nft_resolve_transfer"));
+        assert!(contract.get_items_by_owner(accounts.owner).is_empty());
+    }
+
+    #[test]
+    fn test_transfer_call_by_operator_names_it_as_sender() {
+        let (mut contract, accounts) = setup_transfer_call_contract();
+        contract.nft_approve("item1".to_string(), accounts.alice.clone(), None);
+
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.nft_transfer_call(
+            "market.near".parse().unwrap(),
+            "item1".to_string(),
+            "".to_string(),
+        );
+        let args: Vec<Value> = get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id.as_str() == "market.near")
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                VmAction::FunctionCall { args, .. } => serde_json::from_slice(&args).ok(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(args.len(), 1);
+        assert_eq!(args[0]["sender_id"], accounts.alice.as_str());
+        assert_eq!(args[0]["previous_owner_id"], accounts.owner.as_str());
+    }
+
+    #[test]
+    fn test_transfer_call_to_disallowed_receiver_fails() {
+        let (mut contract, accounts) = setup_transfer_call_contract();
+        contract.add_allowed_receiver("market.near".parse().unwrap());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_transfer_call(
+                "scam.near".parse().unwrap(),
+                "item1".to_string(),
+                "".to_string(),
+            );
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.owner
+        );
+    }
+
+    #[test]
+    fn test_transfer_call_without_allowlist_accepts_any_receiver() {
+        let (mut contract, _) = setup_transfer_call_contract();
+        assert_eq!(contract.get_receiver_allowlist(), None);
+
+        contract.nft_transfer_call(
+            "anything.near".parse().unwrap(),
+            "item1".to_string(),
+            "".to_string(),
+        );
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id.as_str(),
+            "anything.near"
+        );
+    }
+
+    #[test]
+    fn test_resolve_transfer_returns_item_on_request() {
+        let (mut contract, accounts) = setup_transfer_call_contract();
+        let receiver: AccountId = "market.near".parse().unwrap();
+        contract.nft_transfer_call(receiver.clone(), "item1".to_string(), "".to_string());
+
+        // The receiver answered `true`: it wants the item returned
+        let contract_id = env::current_account_id();
+        testing_env!(
+            get_context(contract_id).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"true".to_vec())],
+        );
+        let kept = contract.nft_resolve_transfer(
+            accounts.owner.clone(),
+            receiver.clone(),
+            "item1".to_string(),
+        );
+        assert!(!kept);
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.owner
+        );
+        assert!(contract.get_items_by_owner(receiver).is_empty());
+    }
+
+    #[test]
+    fn test_items_by_rarity() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        let drops = [
+            ("sword", Rarity::Legendary),
+            ("dagger", Rarity::Common),
+            ("axe", Rarity::Legendary),
+            ("bow", Rarity::Rare),
+            ("staff", Rarity::Legendary),
+        ];
+        for (id, rarity) in drops {
+            contract.mint(
+                id.to_string(),
+                "weapon".to_string(),
+                "{}".to_string(),
+                None,
+                None,
+                None,
+                Some(rarity),
+            );
+        }
+
+        let legendary = ids_of(contract.items_by_rarity(Rarity::Legendary, None, None));
+        assert_eq!(legendary, vec!["axe", "staff", "sword"]);
+        let first_page = ids_of(contract.items_by_rarity(Rarity::Legendary, None, Some(2)));
+        let second_page =
+            ids_of(contract.items_by_rarity(Rarity::Legendary, Some("staff".to_string()), Some(2)));
+        assert_eq!(first_page, vec!["axe", "staff"]);
+        assert_eq!(second_page, vec!["sword"]);
+
+        assert_eq!(
+            ids_of(contract.items_by_rarity(Rarity::Common, None, None)),
+            vec!["dagger"]
+        );
+        assert_eq!(
+            ids_of(contract.items_by_rarity(Rarity::Rare, None, None)),
+            vec!["bow"]
+        );
+        assert!(contract
+            .items_by_rarity(Rarity::Epic, None, None)
+            .is_empty());
+
+        // Burning drops the item from its rarity listing
+        let axe = contract.items.get(&"axe".to_string()).unwrap();
+        contract.internal_burn(axe);
+        assert_eq!(
+            ids_of(contract.items_by_rarity(Rarity::Legendary, None, None)),
+            vec!["staff", "sword"]
+        );
+    }
+
+    fn setup_pull_contract() -> (GameItems, TestAccounts) {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        contract.set_pull_transfers(true);
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        (contract, accounts)
+    }
+
+    #[test]
+    fn test_pull_transfer_stays_pending_until_accepted() {
+        let (mut contract, accounts) = setup_pull_contract();
+
+        assert_eq!(
+            contract.get_pending_transfer(accounts.alice.clone(), "item1".to_string()),
+            Some(accounts.owner.clone())
+        );
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.owner
+        );
+        assert!(contract
+            .get_items_by_owner(accounts.alice.clone())
+            .is_empty());
+
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.accept_transfer("item1".to_string());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.alice
+        );
+        assert_eq!(
+            contract.get_pending_transfer(accounts.alice.clone(), "item1".to_string()),
+            None
+        );
+        assert!(contract.get_items_by_owner(accounts.owner).is_empty());
+    }
+
+    #[test]
+    fn test_rejected_pull_transfer_leaves_item_with_sender() {
+        let (mut contract, accounts) = setup_pull_contract();
+
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.reject_transfer("item1".to_string());
+        assert_eq!(
+            contract.get_pending_transfer(accounts.alice.clone(), "item1".to_string()),
+            None
+        );
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.owner
+        );
+
+        // Nothing left to accept
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.accept_transfer("item1".to_string());
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_pull_transfer_holds_one_offer_and_its_fee() {
+        let (mut contract, accounts) = setup_fee_contract();
+        let carol: AccountId = "carol.near".parse().unwrap();
+        contract.set_pull_transfers(true);
+        let offer = |contract: &mut GameItems, from: &AccountId, to: &AccountId| {
+            let mut context = get_context(from.clone());
+            testing_env!(context.attached_deposit(100).build());
+            contract.transfer("item1".to_string(), to.clone());
+        };
+
+        // The fee is held while the offer is pending, and only one offer stands at a time
+        offer(&mut contract, &accounts.alice, &accounts.bob);
+        assert!(transfer_amounts_to("treasury.near").is_empty());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            offer(&mut contract, &accounts.alice, &carol)
+        }));
+        assert!(result.is_err());
+
+        // Rejected and cancelled offers refund the fee
+        testing_env!(get_context(accounts.bob.clone()).build());
+        contract.reject_transfer("item1".to_string());
+        assert_eq!(transfer_amounts_to(accounts.alice.as_str()), vec![100]);
+        offer(&mut contract, &accounts.alice, &carol);
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.cancel_transfer("item1".to_string());
+        assert_eq!(transfer_amounts_to(accounts.alice.as_str()), vec![100]);
+        assert_eq!(
+            contract.get_pending_transfer(carol.clone(), "item1".to_string()),
+            None
+        );
+
+        // An accepted offer pays the fee to the treasury
+        offer(&mut contract, &accounts.alice, &accounts.bob);
+        testing_env!(get_context(accounts.bob.clone()).build());
+        contract.accept_transfer("item1".to_string());
+        assert_eq!(transfer_amounts_to("treasury.near"), vec![100]);
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.bob
+        );
+
+        // An offer lapses, fee refunded, once the item changes hands some other way
+        offer(&mut contract, &accounts.bob, &accounts.alice);
+        testing_env!(get_context(accounts.owner.clone()).build());
+        contract.force_transfer("item1".to_string(), carol.clone());
+        assert_eq!(transfer_amounts_to(accounts.bob.as_str()), vec![100]);
+        assert_eq!(
+            contract.get_pending_transfer(accounts.alice.clone(), "item1".to_string()),
+            None
+        );
+        testing_env!(get_context(accounts.alice.clone()).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.accept_transfer("item1".to_string())
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.get_item("item1".to_string()).owner_id, carol);
+    }
+
+    #[test]
+    fn test_only_the_receiver_can_accept_a_pull_transfer() {
+        let (mut contract, accounts) = setup_pull_contract();
+
+        testing_env!(get_context(accounts.bob.clone()).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.accept_transfer("item1".to_string());
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.owner
+        );
+    }
+
+    fn logged_events() -> Vec<Value> {
+        get_logs()
+            .iter()
+            .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+            .map(|payload| near_sdk::serde_json::from_str(payload).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_reveal_once() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        contract.mint(
+            "box1".to_string(),
+            "weapon".to_string(),
+            "{\"name\":\"Mystery box\"}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        // Unrevealed items can still change hands
+        contract.transfer("box1".to_string(), accounts.alice.clone());
+        testing_env!(get_context(owner.clone()).build());
+
+        contract.reveal(
+            "box1".to_string(),
+            "{\"name\":\"Dragon sword\"}".to_string(),
+        );
+        let item = contract.get_item("box1".to_string());
+        assert!(item.revealed);
+        assert_eq!(item.metadata, "{\"name\":\"Dragon sword\"}");
+        assert!(contract.verify_metadata("box1".to_string()));
+
+        assert_eq!(
+            logged_events(),
+            vec![
+                json!({
+                    "standard": "game_items",
+                    "version": "1.0.0",
+                    "event": "metadata_update",
+                    "data": [{"schema_version": 1, "token_ids": ["box1"]}],
+                }),
+                json!({
+                    "standard": "game_items",
+                    "version": "1.0.0",
+                    "event": "reveal",
+                    "data": [{"schema_version": 1, "token_ids": ["box1"]}],
+                })
+            ]
+        );
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.reveal("box1".to_string(), "{\"name\":\"Stick\"}".to_string());
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_metadata("box1".to_string()),
+            Some("{\"name\":\"Dragon sword\"}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_transfer_count_and_limit() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        contract.set_max_transfers(Some(2));
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(contract.transfers_remaining("item1".to_string()), Some(2));
+
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.transfer("item1".to_string(), accounts.bob.clone());
+        assert_eq!(contract.get_item("item1".to_string()).transfer_count, 2);
+        assert_eq!(contract.transfers_remaining("item1".to_string()), Some(0));
+
+        testing_env!(get_context(accounts.bob.clone()).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("item1".to_string(), owner.clone());
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.bob
+        );
+    }
+
+    #[test]
+    fn test_split_keeps_transfer_count() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        contract.set_max_transfers(Some(1));
+        mint_stack(&mut contract, "arrows", 50);
+        contract.transfer("arrows".to_string(), accounts.alice.clone());
+
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.split("arrows".to_string(), "arrows2".to_string(), 20);
+        let split_off = contract.get_item("arrows2".to_string());
+        assert_eq!(split_off.transfer_count, 1);
+        assert_eq!(split_off.transfer_log, vec![accounts.owner.clone()]);
+        assert_eq!(contract.transfers_remaining("arrows2".to_string()), Some(0));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("arrows2".to_string(), accounts.bob.clone());
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_transfers_are_unlimited_by_default() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(contract.transfers_remaining("item1".to_string()), None);
+
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        for _ in 0..5 {
+            testing_env!(get_context(accounts.alice.clone()).build());
+            contract.transfer("item1".to_string(), accounts.bob.clone());
+            testing_env!(get_context(accounts.bob.clone()).build());
+            contract.transfer("item1".to_string(), accounts.alice.clone());
+        }
+        assert_eq!(contract.get_item("item1".to_string()).transfer_count, 11);
+    }
+
+    // Mint a plain unique weapon as the contract owner
+    fn mint_item(contract: &mut GameItems, id: &str) {
+        contract.mint(
+            id.to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_export_items_pages_without_gaps() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        let mut minted: Vec<String> = (0..23).map(|i| format!("item{:02}", i)).collect();
+        // One mint per transaction, as on chain, to stay within the gas of a single call
+        for id in minted.iter().rev() {
+            testing_env!(get_context(owner.clone()).build());
+            mint_item(&mut contract, id);
+        }
+
+        let mut exported: Vec<String> = Vec::new();
+        loop {
+            let page = contract.export_items(exported.last().cloned(), Some(5));
+            if page.is_empty() {
+                break;
+            }
+            assert!(page.len() <= 5);
+            exported.extend(ids_of(page));
+            // Items minted or burned behind the cursor don't shift later pages
+            if exported.len() == 10 {
+                testing_env!(get_context(owner.clone()).build());
+                mint_item(&mut contract, "item");
+                let first = contract.items.get(&"item00".to_string()).unwrap();
+                contract.internal_burn(first);
+            }
+        }
+        minted.sort();
+        minted.sort();
+        assert_eq!(exported, minted);
+    }
+
+    #[test]
+    fn test_export_owners_reports_counts() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["item1", "item2", "item3", "item4"] {
+            mint_item(&mut contract, id);
+        }
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        contract.transfer("item2".to_string(), accounts.alice.clone());
+        contract.transfer("item3".to_string(), accounts.bob.clone());
+
+        assert_eq!(
+            contract.export_owners(None, None),
+            vec![
+                (accounts.alice.clone(), 2),
+                (accounts.bob.clone(), 1),
+                (owner.clone(), 1),
+            ]
+        );
+        assert_eq!(
+            contract.export_owners(Some(accounts.alice.clone()), Some(1)),
+            vec![(accounts.bob.clone(), 1)]
+        );
+
+        // Accounts that no longer hold anything drop out of the export
+        testing_env!(get_context(accounts.bob.clone()).build());
+        contract.transfer("item3".to_string(), accounts.alice.clone());
+        assert_eq!(
+            contract.export_owners(None, None),
+            vec![(accounts.alice.clone(), 3), (owner, 1)]
+        );
+    }
+
+    #[test]
+    fn test_redeem_credits_and_claims_rewards() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        mint_item(&mut contract, "item1");
+        mint_item(&mut contract, "item2");
+        contract.set_redeem_value("item1".to_string(), Some(U128(300)));
+        contract.set_redeem_value("item2".to_string(), Some(U128(200)));
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        contract.transfer("item2".to_string(), accounts.alice.clone());
+
+        testing_env!(get_context(accounts.alice.clone()).build());
+        assert_eq!(contract.redeem("item1".to_string()), U128(300));
+        assert_eq!(contract.redeem("item2".to_string()), U128(200));
+        assert_eq!(
+            contract.get_item_status("item1".to_string()),
+            ItemStatus::Unknown
+        );
+        assert!(contract
+            .get_items_by_owner(accounts.alice.clone())
+            .is_empty());
+        assert_eq!(
+            contract.get_reward_balance(accounts.alice.clone()),
+            U128(500)
+        );
+
+        contract.claim_rewards();
+        assert_eq!(transfer_amounts_to(accounts.alice.as_str()), vec![500]);
+        assert_eq!(contract.get_reward_balance(accounts.alice), U128(0));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.claim_rewards();
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_redeem_zero_value_item() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+
+        assert_eq!(contract.redeem("item1".to_string()), U128(0));
+        assert_eq!(
+            contract.get_item_status("item1".to_string()),
+            ItemStatus::Unknown
+        );
+        assert_eq!(contract.get_reward_balance(owner), U128(0));
+    }
+
+    #[test]
+    fn test_redeem_requires_item_owner() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        contract.set_redeem_value("item1".to_string(), Some(U128(300)));
+
+        testing_env!(get_context(accounts.bob.clone()).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.redeem("item1".to_string());
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.get_item("item1".to_string()).owner_id, owner);
+        assert_eq!(contract.get_reward_balance(accounts.bob), U128(0));
+    }
+
+    #[test]
+    fn test_mint_requires_registered_category() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = GameItems::new(owner, None);
+        contract.register_category("potion".to_string());
+        contract.mint(
+            "item1".to_string(),
+            "potion".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(contract.get_item("item1".to_string()).item_type, "potion");
+        assert_eq!(contract.get_categories(), vec!["potion".to_string()]);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.mint(
+                "item2".to_string(),
+                "potoin".to_string(),
+                "{}".to_string(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_item_status("item2".to_string()),
+            ItemStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn test_unregister_category_keeps_minted_items() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        mint_item(&mut contract, "item1");
+        contract.unregister_category("weapon".to_string());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            mint_item(&mut contract, "item2");
+        }));
+        assert!(result.is_err());
+
+        // The existing weapon is still readable and transferable
+        assert_eq!(contract.get_item("item1".to_string()).item_type, "weapon");
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.alice
+        );
+    }
+
+    #[test]
+    fn test_token_access_composes_owner_approvals_and_flags() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        contract.nft_approve("item1".to_string(), accounts.alice.clone(), None);
+        contract.nft_approve(
+            "item1".to_string(),
+            accounts.bob.clone(),
+            Some("list".to_string()),
+        );
+        contract.lock_item("item1".to_string());
+        contract.set_soulbound("item1".to_string(), true);
+
+        let mut approved_account_ids = HashMap::new();
+        approved_account_ids.insert(accounts.alice.clone(), 0);
+        approved_account_ids.insert(accounts.bob.clone(), 1);
+        assert_eq!(
+            contract.token_access("item1".to_string()),
+            Some(TokenAccess {
+                owner_id: owner,
+                approved_account_ids,
+                locked: true,
+                soulbound: true,
+            })
+        );
+        assert_eq!(
+            function_calls_to(accounts.bob.as_str())
+                .into_iter()
+                .map(|(method, _)| method)
+                .collect::<Vec<_>>(),
+            vec!["nft_on_approve".to_string()]
+        );
+        assert_eq!(contract.token_access("missing".to_string()), None);
+    }
+
+    #[test]
+    fn test_approved_operator_transfers_and_approvals_reset() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        mint_item(&mut contract, "item1");
+        contract.nft_approve("item1".to_string(), accounts.alice.clone(), None);
+        assert!(contract.nft_is_approved("item1".to_string(), accounts.alice.clone(), Some(0)));
+        assert!(!contract.nft_is_approved("item1".to_string(), accounts.alice.clone(), Some(1)));
+
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.transfer("item1".to_string(), accounts.bob.clone());
+        let access = contract.token_access("item1".to_string()).unwrap();
+        assert_eq!(access.owner_id, accounts.bob);
+        assert!(access.approved_account_ids.is_empty());
+
+        // The old approval doesn't carry over to the new holder
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("item1".to_string(), accounts.alice.clone());
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_soulbound_item_cannot_be_transferred() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        contract.set_soulbound("item1".to_string(), true);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("item1".to_string(), accounts.alice.clone());
+        }));
+        assert!(result.is_err());
+
+        contract.set_soulbound("item1".to_string(), false);
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.alice
+        );
+    }
+
+    #[test]
+    fn test_split_off_stays_soulbound() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        mint_stack(&mut contract, "arrows", 50);
+        mint_stack(&mut contract, "quiver", 10);
+        contract.set_soulbound("arrows".to_string(), true);
+        contract.lock_metadata("arrows".to_string());
+
+        contract.split("arrows".to_string(), "arrows2".to_string(), 20);
+        let split_off = contract.get_item("arrows2".to_string());
+        assert!(split_off.soulbound);
+        assert!(split_off.metadata_locked);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("arrows2".to_string(), accounts.alice.clone());
+        }));
+        assert!(result.is_err());
+
+        // Merging can't lift the units of a soulbound stack into a transferable one
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.merge("quiver".to_string(), "arrows2".to_string());
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.get_item("quiver".to_string()).quantity(), 10);
+    }
+
+    #[test]
+    fn test_decimals_round_trip_per_item_type() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.register_category("gold".to_string());
+        assert_eq!(contract.get_decimals("gold".to_string()), 0);
+
+        contract.set_decimals("gold".to_string(), 2);
+        assert_eq!(contract.get_decimals("gold".to_string()), 2);
+        assert_eq!(contract.get_decimals("weapon".to_string()), 0);
+
+        for (item_type, decimals) in [("gold", MAX_DECIMALS + 1), ("potoin", 2)] {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.set_decimals(item_type.to_string(), decimals);
+            }));
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_split_and_merge_ignore_decimals() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.register_category("gold".to_string());
+        contract.set_decimals("gold".to_string(), 2);
+        contract.mint(
+            "gold".to_string(),
+            "gold".to_string(),
+            "{}".to_string(),
+            Some(1050),
+            None,
+            None,
+            None,
+        );
+
+        contract.split("gold".to_string(), "gold2".to_string(), 25);
+        assert_eq!(contract.get_item("gold".to_string()).quantity(), 1025);
+        assert_eq!(contract.get_item("gold2".to_string()).quantity(), 25);
+
+        contract.merge("gold".to_string(), "gold2".to_string());
+        assert_eq!(contract.get_item("gold".to_string()).quantity(), 1050);
+        assert_eq!(contract.get_decimals("gold".to_string()), 2);
+    }
+
+    #[test]
+    fn test_max_metadata_bytes() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        assert_eq!(
+            contract.get_max_metadata_bytes(),
+            DEFAULT_MAX_METADATA_BYTES
+        );
+        let at_limit = "x".repeat(DEFAULT_MAX_METADATA_BYTES as usize);
+        let over_limit = format!("{}x", at_limit);
+
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            at_limit.clone(),
+            None,
+            None,
+            None,
+            None,
+        );
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.mint(
+                "item2".to_string(),
+                "weapon".to_string(),
+                over_limit.clone(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.update_metadata("item1".to_string(), over_limit.clone());
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.get_item("item1".to_string()).metadata, at_limit);
+
+        contract.set_max_metadata_bytes(DEFAULT_MAX_METADATA_BYTES + 1);
+        contract.update_metadata("item1".to_string(), over_limit.clone());
+        assert_eq!(contract.get_item("item1".to_string()).metadata, over_limit);
+    }
+
+    #[test]
+    fn test_mint_and_list() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        contract.mint_and_list(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            U128(500),
+        );
+
+        assert_eq!(contract.get_item("item1".to_string()).owner_id, owner);
+        assert_eq!(
+            contract.get_listing("item1".to_string()),
+            Some(Listing {
+                seller_id: owner.clone(),
+                price: U128(500),
+            })
+        );
+        assert_eq!(
+            logged_events()
+                .iter()
+                .map(|event| event["event"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>(),
+            vec!["mint", "listing"]
+        );
+
+        let mut context = get_context(accounts.alice.clone());
+        testing_env!(context.attached_deposit(600).build());
+        contract.buy_item("item1".to_string());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.alice
+        );
+        assert_eq!(contract.get_listing("item1".to_string()), None);
+        assert_eq!(transfer_amounts_to(owner.as_str()), vec![500]);
+        assert_eq!(transfer_amounts_to(accounts.alice.as_str()), vec![100]);
+    }
+
+    #[test]
+    fn test_mint_and_list_duplicate_id_reverts() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        mint_item(&mut contract, "item1");
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.mint_and_list(
+                "item1".to_string(),
+                "weapon".to_string(),
+                "{}".to_string(),
+                U128(500),
+            );
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.get_listing("item1".to_string()), None);
+    }
+
+    #[test]
+    fn test_transfer_clears_listing() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        contract.list_item("item1".to_string(), U128(500));
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        assert_eq!(contract.get_listing("item1".to_string()), None);
+
+        let mut context = get_context(accounts.bob.clone());
+        testing_env!(context.attached_deposit(500).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.buy_item("item1".to_string());
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.alice
+        );
+    }
+
+    #[test]
+    fn test_burn_clears_listing() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        mint_item(&mut contract, "item1");
+        contract.list_item("item1".to_string(), U128(500));
+        contract.redeem("item1".to_string());
+        assert_eq!(contract.get_listing("item1".to_string()), None);
+    }
+
+    #[test]
+    fn test_lock_minting_is_permanent() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        contract.reserve_lazy(
+            vec!["lazy1".to_string()],
+            "armor".to_string(),
+            "{}".to_string(),
+        );
+
+        testing_env!(get_context(owner.clone()).build());
+        contract.lock_minting();
+        assert!(contract.is_minting_locked());
+        assert_eq!(
+            logged_events(),
+            vec![json!({
+                "standard": "game_items",
+                "version": "1.0.0",
+                "event": "minting_locked",
+                "data": [{"schema_version": 1, "owner_id": "owner.near"}],
+            })]
+        );
+
+        let attempts: [fn(&mut GameItems); 5] = [
+            |contract| mint_item(contract, "item2"),
+            |contract| {
+                contract.mint_and_list(
+                    "item3".to_string(),
+                    "weapon".to_string(),
+                    "{}".to_string(),
+                    U128(500),
+                )
+            },
+            |contract| {
+                contract.reserve_lazy(
+                    vec!["lazy2".to_string()],
+                    "armor".to_string(),
+                    "{}".to_string(),
+                )
+            },
+            |contract| {
+                contract.claim_lazy("lazy1".to_string(), get_accounts().alice);
+            },
+            // Locking again doesn't toggle anything back
+            |contract| contract.lock_minting(),
+        ];
+        for attempt in attempts {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                attempt(&mut contract);
+            }));
+            assert!(result.is_err());
+        }
+        assert!(contract.is_minting_locked());
+        assert_eq!(
+            contract.get_item_status("lazy1".to_string()),
+            ItemStatus::Reserved
+        );
+
+        // Existing items keep working
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.alice
+        );
+    }
+
+    #[test]
+    fn test_find_duplicate_names() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        for (id, name) in [("a", "Axe"), ("b", "Sword"), ("c", "Bow"), ("d", "Shield")] {
+            contract.mint(
+                id.to_string(),
+                "weapon".to_string(),
+                format!("{{\"name\":\"{}\"}}", name),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+        assert!(contract.find_duplicate_names(None, None).is_empty());
+
+        contract.update_metadata("a".to_string(), "{\"name\":\"Bow\"}".to_string());
+        contract.update_metadata("d".to_string(), "{\"name\":\"Sword\"}".to_string());
+        assert_eq!(
+            contract.find_duplicate_names(None, None),
+            vec![
+                ("Bow".to_string(), vec!["a".to_string(), "c".to_string()]),
+                ("Sword".to_string(), vec!["b".to_string(), "d".to_string()]),
+            ]
+        );
+        // Names are scanned in order: "Bow" first, then "Sword"
+        assert_eq!(
+            contract.find_duplicate_names(Some(U128(1)), Some(1)),
+            vec![("Sword".to_string(), vec!["b".to_string(), "d".to_string()])]
+        );
+    }
+
+    #[test]
+    fn test_find_duplicate_names_after_cursor() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        for (id, name) in [("a", "Bow"), ("b", "Sword"), ("c", "Bow"), ("d", "Sword")] {
+            contract.mint(
+                id.to_string(),
+                "weapon".to_string(),
+                format!("{{\"name\":\"{}\"}}", name),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+        assert_eq!(
+            contract.find_duplicate_names_after(None, None),
+            DuplicateNamesPage {
+                duplicates: vec![
+                    ("Bow".to_string(), vec!["a".to_string(), "c".to_string()]),
+                    ("Sword".to_string(), vec!["b".to_string(), "d".to_string()]),
+                ],
+                next: None,
+            }
+        );
+        // Items are scanned by (name, id): the page ends inside "Sword" and the next page
+        // still reports the rest of it
+        let first = contract.find_duplicate_names_after(None, Some(3));
+        assert_eq!(
+            first.duplicates,
+            vec![
+                ("Bow".to_string(), vec!["a".to_string(), "c".to_string()]),
+                ("Sword".to_string(), vec!["b".to_string()]),
+            ]
+        );
+        assert_eq!(first.next, Some(("Sword".to_string(), "b".to_string())));
+        assert_eq!(
+            contract.find_duplicate_names_after(first.next, Some(3)),
+            DuplicateNamesPage {
+                duplicates: vec![("Sword".to_string(), vec!["d".to_string()])],
+                next: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_find_duplicate_names_clean_state() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        assert!(contract.find_duplicate_names(None, None).is_empty());
+
+        for (id, name) in [("a", "Sword"), ("b", "Sword")] {
+            contract.mint(
+                id.to_string(),
+                "weapon".to_string(),
+                format!("{{\"name\":\"{}\"}}", name),
+                None,
+                None,
+                Some(true),
+                None,
+            );
+        }
+        let burned = contract.items.get(&"b".to_string()).unwrap();
+        contract.internal_burn(burned);
+        assert!(contract.find_duplicate_names(None, None).is_empty());
+    }
+
+    #[test]
+    fn test_observer_notified_on_transfer() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+        let observer: AccountId = "mirror.near".parse().unwrap();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        mint_item(&mut contract, "item2");
+
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        assert!(get_created_receipts().is_empty());
+
+        contract.set_observer(Some(observer.clone()));
+        contract.transfer("item2".to_string(), accounts.alice.clone());
+        assert_eq!(
+            function_calls_to(observer.as_str()),
+            vec![(
+                "on_item_transfer".to_string(),
+                DEFAULT_GAS_FOR_ON_ITEM_TRANSFER
+            )]
+        );
+        assert_eq!(
+            contract.get_item("item2".to_string()).owner_id,
+            accounts.alice
+        );
+    }
+
+    #[test]
+    fn test_inventory_diff() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["a1", "a2", "a3", "b1"] {
+            mint_item(&mut contract, id);
+        }
+        for id in ["a1", "a2", "a3"] {
+            contract.transfer(id.to_string(), accounts.alice.clone());
+        }
+        contract.transfer("b1".to_string(), accounts.bob.clone());
+
+        let (alice_only, bob_only) =
+            contract.inventory_diff(accounts.alice.clone(), accounts.bob.clone(), None, None);
+        assert_eq!(alice_only, vec!["a1", "a2", "a3"]);
+        assert_eq!(bob_only, vec!["b1"]);
+
+        let (alice_only, bob_only) = contract.inventory_diff(
+            accounts.alice.clone(),
+            accounts.bob.clone(),
+            Some(U128(1)),
+            Some(1),
+        );
+        assert_eq!(alice_only, vec!["a2"]);
+        assert!(bob_only.is_empty());
+
+        // Nothing the owner holds shows up on either side
+        assert_eq!(contract.get_items_by_owner(owner), Vec::<String>::new());
+        let (same_a, same_b) =
+            contract.inventory_diff(accounts.alice.clone(), accounts.alice, None, None);
+        assert!(same_a.is_empty() && same_b.is_empty());
+    }
+
+    // Register a deterministic test key for `account` and return it for signing
+    fn register_signing_key(
+        contract: &mut GameItems,
+        account: &AccountId,
+        seed: u8,
+    ) -> ed25519_dalek::Keypair {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[seed; 32]).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        let mut key = vec![CurveType::ED25519 as u8];
+        key.extend_from_slice(public.as_bytes());
+
+        testing_env!(get_context(account.clone()).build());
+        contract.set_signing_key(PublicKey::try_from(key).unwrap());
+        ed25519_dalek::Keypair { secret, public }
+    }
+
+    fn sign_transfer(
+        contract: &GameItems,
+        keypair: &ed25519_dalek::Keypair,
+        id: &str,
+        to: &AccountId,
+        nonce: u64,
+    ) -> Base64VecU8 {
+        use ed25519_dalek::Signer;
+        let message = contract.transfer_message(id.to_string(), to.clone(), nonce);
+        Base64VecU8(keypair.sign(message.as_bytes()).to_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_transfer_with_signature_by_relayer() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+        let relayer: AccountId = "relayer.near".parse().unwrap();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        mint_item(&mut contract, "item1");
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        let keypair = register_signing_key(&mut contract, &accounts.alice, 7);
+
+        let signature = sign_transfer(&contract, &keypair, "item1", &accounts.bob, 1);
+        testing_env!(get_context(relayer).build());
+        contract.transfer_with_signature(
+            "item1".to_string(),
+            accounts.bob.clone(),
+            1,
+            signature.clone(),
+        );
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.bob
+        );
+
+        // Replaying the same authorization fails even once alice holds the item again
+        testing_env!(get_context(accounts.bob.clone()).build());
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer_with_signature(
+                "item1".to_string(),
+                accounts.bob.clone(),
+                1,
+                signature.clone(),
+            );
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.alice
+        );
+    }
+
+    #[test]
+    fn test_transfer_with_bad_signature() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        mint_item(&mut contract, "item1");
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        register_signing_key(&mut contract, &accounts.alice, 7);
+        let impostor = register_signing_key(&mut contract, &accounts.bob, 9);
+
+        let signatures = [
+            // Signed by the wrong key
+            sign_transfer(&contract, &impostor, "item1", &accounts.bob, 1),
+            Base64VecU8(vec![0; 64]),
+            Base64VecU8(vec![1, 2, 3]),
+        ];
+        for signature in signatures {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.transfer_with_signature(
+                    "item1".to_string(),
+                    accounts.bob.clone(),
+                    1,
+                    signature.clone(),
+                );
+            }));
+            assert!(result.is_err());
+        }
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.alice
+        );
+    }
+
+    // Owner mints item1 and it passes to alice and then bob, who lists it for 1000
+    fn setup_resale_contract() -> (GameItems, TestAccounts) {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.transfer("item1".to_string(), accounts.bob.clone());
+        testing_env!(get_context(accounts.bob.clone()).build());
+        contract.list_item("item1".to_string(), U128(1000));
+        (contract, accounts)
+    }
+
+    // Mint one item per id for `holder`, which weighs their previous-holder royalty share
+    fn give_items(contract: &mut GameItems, ids: &[&str], holder: &AccountId) {
+        for id in ids {
+            testing_env!(get_context(contract.owner_id.clone()).build());
+            mint_item(contract, id);
+            if *holder != contract.owner_id {
+                contract.transfer(id.to_string(), holder.clone());
+            }
+        }
+    }
+
+    #[test]
+    fn test_holder_royalty_split_across_previous_owners() {
+        let (mut contract, accounts) = setup_resale_contract();
+        assert_eq!(
+            contract.get_item("item1".to_string()).transfer_log,
+            vec![accounts.owner.clone(), accounts.alice.clone()]
+        );
+        // The owner holds one item and alice three, so alice gets three times the share
+        give_items(&mut contract, &["item2"], &accounts.owner);
+        give_items(&mut contract, &["item3", "item4", "item5"], &accounts.alice);
+        testing_env!(get_context(accounts.owner.clone()).build());
+        contract.set_holder_royalty(Some(HolderRoyalty {
+            bps: 1000,
+            depth: 2,
+        }));
+
+        let mut context = get_context("carol.near".parse().unwrap());
+        testing_env!(context.attached_deposit(1000).build());
+        contract.buy_item("item1".to_string());
+        assert_eq!(contract.royalties_owed(accounts.owner.clone()), U128(25));
+        assert_eq!(contract.royalties_owed(accounts.alice.clone()), U128(75));
+        assert_eq!(transfer_amounts_to(accounts.bob.as_str()), vec![900]);
+    }
+
+    #[test]
+    fn test_holder_royalty_depth_caps_recipients() {
+        let (mut contract, accounts) = setup_resale_contract();
+        give_items(&mut contract, &["item2"], &accounts.owner);
+        give_items(&mut contract, &["item3"], &accounts.alice);
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.set_holder_royalty(Some(HolderRoyalty {
+                bps: 1000,
+                depth: MAX_HOLDER_ROYALTY_DEPTH + 1,
+            }));
+        }));
+        assert!(result.is_err());
+        contract.set_holder_royalty(Some(HolderRoyalty {
+            bps: 1000,
+            depth: 1,
+        }));
+
+        let mut context = get_context("carol.near".parse().unwrap());
+        testing_env!(context.attached_deposit(1000).build());
+        contract.buy_item("item1".to_string());
+        assert_eq!(contract.royalties_owed(accounts.owner.clone()), U128(0));
+        assert_eq!(contract.royalties_owed(accounts.alice.clone()), U128(100));
+        assert_eq!(transfer_amounts_to(accounts.bob.as_str()), vec![900]);
+    }
+
+    #[test]
+    fn test_recategorize_moves_type_index() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.register_category("misc".to_string());
+        for (id, item_type) in [("item1", "misc"), ("item2", "misc"), ("item3", "weapon")] {
+            contract.mint(
+                id.to_string(),
+                item_type.to_string(),
+                "{}".to_string(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+
+        contract.recategorize("item1".to_string(), "weapon".to_string());
+        assert_eq!(contract.get_item("item1".to_string()).item_type, "weapon");
+        assert_eq!(
+            ids_of(contract.items_by_type("misc".to_string(), None, None)),
+            vec!["item2"]
+        );
+        let mut weapons = ids_of(contract.items_by_type("weapon".to_string(), None, None));
+        weapons.sort();
+        assert_eq!(weapons, vec!["item1", "item3"]);
+
+        // "potion" isn't a registered category
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.recategorize("item2".to_string(), "potion".to_string());
+        }));
+        assert!(result.is_err());
+        assert!(contract
+            .items_by_type("potion".to_string(), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_nft_approve_many_skips_items_not_owned() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+        let market: AccountId = "market.near".parse().unwrap();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["item1", "item2", "item3"] {
+            mint_item(&mut contract, id);
+        }
+        contract.transfer("item2".to_string(), accounts.alice.clone());
+
+        testing_env!(get_context(owner.clone()).build());
+        let approved = contract.nft_approve_many(
+            vec![
+                "item1".to_string(),
+                "item2".to_string(),
+                "item3".to_string(),
+                "missing".to_string(),
+            ],
+            market.clone(),
+            None,
+        );
+        assert_eq!(approved, vec!["item1", "item3"]);
+        assert!(contract.nft_is_approved("item1".to_string(), market.clone(), None));
+        assert!(!contract.nft_is_approved("item2".to_string(), market.clone(), None));
+        assert!(contract.nft_is_approved("item3".to_string(), market, None));
+        assert_eq!(
+            logged_events(),
+            vec![json!({
+                "standard": "game_items",
+                "version": "1.0.0",
+                "event": "approve",
+                "data": [{
+                    "schema_version": 1,
+                    "owner_id": "owner.near",
+                    "account_id": "market.near",
+                    "token_ids": ["item1", "item3"],
+                }],
+            })]
+        );
+    }
+
+    #[test]
+    fn test_sweep_account_skips_locked_items() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["item1", "item2", "item3", "item4"] {
+            mint_item(&mut contract, id);
+            contract.transfer(id.to_string(), accounts.alice.clone());
+        }
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.lock_item("item2".to_string());
+
+        testing_env!(get_context(owner.clone()).build());
+        assert_eq!(
+            contract.sweep_account(accounts.alice.clone(), accounts.bob.clone(), Some(2)),
+            SweepResult {
+                moved: 2,
+                remaining: 2
+            }
+        );
+        assert_eq!(
+            contract.sweep_account(accounts.alice.clone(), accounts.bob.clone(), Some(2)),
+            SweepResult {
+                moved: 1,
+                remaining: 1
+            }
+        );
+        assert_eq!(
+            contract.sweep_account(accounts.alice.clone(), accounts.bob.clone(), None),
+            SweepResult {
+                moved: 0,
+                remaining: 1
+            }
+        );
+
+        assert_eq!(
+            contract.get_items_by_owner(accounts.alice),
+            vec!["item2".to_string()]
+        );
+        let mut swept = contract.get_items_by_owner(accounts.bob);
+        swept.sort();
+        assert_eq!(swept, vec!["item1", "item3", "item4"]);
+    }
+
+    #[test]
+    fn test_allowed_media_schemes() {
+        let owner = get_accounts().owner;
+        let https = "{\"media\":\"https://cdn.example/sword.png\"}".to_string();
+        let http = "{\"media\":\"http://cdn.example/sword.png\"}".to_string();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        // Unrestricted by default
+        assert!(contract.get_allowed_media_schemes().is_empty());
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            http.clone(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        contract.set_allowed_media_schemes(vec!["https://".to_string(), "ipfs://".to_string()]);
+        contract.mint(
+            "item2".to_string(),
+            "weapon".to_string(),
+            https.clone(),
+            None,
+            None,
+            None,
+            None,
+        );
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.mint(
+                "item3".to_string(),
+                "weapon".to_string(),
+                http.clone(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.update_metadata("item2".to_string(), http.clone());
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.get_item("item2".to_string()).metadata, https);
+
+        // Metadata without media is unaffected, whether the key is missing or null
+        contract.update_metadata("item1".to_string(), "{}".to_string());
+        contract.mint(
+            "item4".to_string(),
+            "weapon".to_string(),
+            "{\"media\":null}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.update_metadata("item4".to_string(), "{\"media\":42}".to_string());
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_supply_breakdown_invariant() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        let assert_invariant = |contract: &GameItems| {
+            let (minted, supply, burned) = contract.supply_breakdown();
+            assert_eq!(minted, supply + burned);
+        };
+        assert_eq!(contract.supply_breakdown(), (0, 0, 0));
+
+        for id in ["item1", "item2", "item3"] {
+            mint_item(&mut contract, id);
+            assert_invariant(&contract);
+        }
+        contract.redeem("item1".to_string());
+        assert_invariant(&contract);
+        contract.bundle(
+            vec!["item2".to_string(), "item3".to_string()],
+            "bundle1".to_string(),
+            "{}".to_string(),
+        );
+        assert_invariant(&contract);
+        contract.unbundle("bundle1".to_string());
+        assert_invariant(&contract);
+
+        assert_eq!(contract.supply_breakdown(), (4, 2, 2));
+    }
+
+    #[test]
+    fn test_attached_children_follow_parent() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["rifle", "scope", "lens"] {
+            mint_item(&mut contract, id);
+        }
+        contract.attach_child("rifle".to_string(), "scope".to_string());
+        contract.attach_child("scope".to_string(), "lens".to_string());
+        assert_eq!(contract.get_children("rifle".to_string()), vec!["scope"]);
+
+        // Attaching the rifle below its own descendant would create a cycle
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.attach_child("lens".to_string(), "rifle".to_string());
+        }));
+        assert!(result.is_err());
+
+        // Attached children can't leave on their own
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("scope".to_string(), accounts.alice.clone());
+        }));
+        assert!(result.is_err());
+
+        contract.transfer("rifle".to_string(), accounts.alice.clone());
+        for id in ["rifle", "scope", "lens"] {
+            assert_eq!(contract.get_item(id.to_string()).owner_id, accounts.alice);
+        }
+        assert!(contract.get_items_by_owner(owner).is_empty());
+    }
+
+    #[test]
+    fn test_detach_child() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "rifle");
+        mint_item(&mut contract, "scope");
+        contract.attach_child("rifle".to_string(), "scope".to_string());
+
+        contract.detach_child("rifle".to_string(), "scope".to_string());
+        assert!(contract.get_children("rifle".to_string()).is_empty());
+        assert_eq!(contract.get_item("scope".to_string()).attached_to, None);
+
+        contract.transfer("rifle".to_string(), accounts.alice.clone());
+        assert_eq!(contract.get_item("scope".to_string()).owner_id, owner);
+        contract.transfer("scope".to_string(), accounts.bob.clone());
+        assert_eq!(
+            contract.get_item("scope".to_string()).owner_id,
+            accounts.bob
+        );
+    }
+
+    #[test]
+    fn test_attached_items_limit() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        contract.set_max_attached(2);
+        for id in ["rifle", "scope", "grip", "lens"] {
+            mint_item(&mut contract, id);
+        }
+        contract.attach_child("rifle".to_string(), "scope".to_string());
+        contract.attach_child("scope".to_string(), "lens".to_string());
+
+        // A third attached item anywhere under the rifle exceeds the limit
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.attach_child("scope".to_string(), "grip".to_string());
+        }));
+        assert!(result.is_err());
+
+        // Within the limit the whole tree moves
+        contract.transfer("rifle".to_string(), accounts.alice.clone());
+        assert_eq!(
+            contract.get_item("lens".to_string()).owner_id,
+            accounts.alice
+        );
+
+        // Lowering the limit blocks the cascade until children are detached
+        contract.set_max_attached(1);
+        testing_env!(get_context(accounts.alice.clone()).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("rifle".to_string(), accounts.bob.clone());
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_item("rifle".to_string()).owner_id,
+            accounts.alice
+        );
+
+        contract.detach_child("scope".to_string(), "lens".to_string());
+        contract.transfer("rifle".to_string(), accounts.bob.clone());
+        assert_eq!(
+            contract.get_item("scope".to_string()).owner_id,
+            accounts.bob
+        );
+        assert_eq!(
+            contract.get_item("lens".to_string()).owner_id,
+            accounts.alice
+        );
+    }
+
+    #[test]
+    fn test_cooldowns_per_action() {
+        let owner = get_accounts().owner;
+        let alice = get_accounts().alice;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["item1", "item2", "item3", "item4"] {
+            mint_item(&mut contract, id);
+        }
+        contract.set_cooldown(TRANSFER_ACTION.to_string(), Some(1_000));
+        contract.set_cooldown(REDEEM_ACTION.to_string(), Some(5_000));
+
+        let mut context = get_context(owner.clone());
+        testing_env!(context.block_timestamp(10_000).build());
+        contract.transfer("item1".to_string(), alice.clone());
+        assert_eq!(
+            contract.cooldown_until(owner.clone(), TRANSFER_ACTION.to_string()),
+            11_000
+        );
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("item2".to_string(), alice.clone());
+        }));
+        assert!(result.is_err());
+
+        // Redeeming has its own cooldown, unaffected by the transfer one
+        contract.redeem("item3".to_string());
+
+        testing_env!(context.block_timestamp(11_000).build());
+        contract.transfer("item2".to_string(), alice.clone());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.redeem("item4".to_string());
+        }));
+        assert!(result.is_err());
+
+        testing_env!(context.block_timestamp(15_000).build());
+        contract.redeem("item4".to_string());
+        assert!(contract.get_items_by_owner(owner).is_empty());
+    }
+
+    #[test]
+    fn test_patch_metadata_merges_shallowly() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{\"name\":\"Sword\",\"power\":10,\"stats\":{\"speed\":3}}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        contract.patch_metadata(
+            "item1".to_string(),
+            "{\"power\":12,\"stats\":{\"weight\":5}}".to_string(),
+        );
+        let metadata: Value =
+            near_sdk::serde_json::from_str(&contract.get_item("item1".to_string()).metadata)
+                .unwrap();
+        assert_eq!(
+            metadata,
+            json!({"name": "Sword", "power": 12, "stats": {"weight": 5}})
+        );
+        assert!(contract.verify_metadata("item1".to_string()));
+
+        for patch in ["[1, 2]", "\"power\"", "not json"] {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.patch_metadata("item1".to_string(), patch.to_string());
+            }));
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_locked_metadata_rejects_patches() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{\"name\":\"Sword\"}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.lock_metadata("item1".to_string());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.patch_metadata("item1".to_string(), "{\"power\":12}".to_string());
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.update_metadata("item1".to_string(), "{}".to_string());
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_metadata("item1".to_string()),
+            Some("{\"name\":\"Sword\"}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_search_items_by_owner() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in [
+            "sword-1", "sword-2", "Sword-3", "shield-1", "sword-4", "sword-5",
+        ] {
+            mint_item(&mut contract, id);
+        }
+        contract.transfer("sword-4".to_string(), accounts.alice.clone());
+
+        // Case-sensitive, and only the owner's own items
+        assert_eq!(
+            contract.search_items_by_owner(owner.clone(), "sword".to_string(), None, None),
+            vec!["sword-1", "sword-2", "sword-5"]
+        );
+        assert!(contract
+            .search_items_by_owner(owner.clone(), "axe".to_string(), None, None)
+            .is_empty());
+
+        assert_eq!(
+            contract.search_items_by_owner(
+                owner.clone(),
+                "sword".to_string(),
+                Some(U128(1)),
+                Some(1)
+            ),
+            vec!["sword-2"]
+        );
+        assert_eq!(
+            contract.search_items_by_owner(
+                owner.clone(),
+                "sword".to_string(),
+                Some(U128(2)),
+                Some(5)
+            ),
+            vec!["sword-5"]
+        );
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.search_items_by_owner(owner.clone(), "-1".to_string(), None, None);
+        }));
+        assert!(result.is_err());
+    }
+
+    fn airdrop_entries(ids: &[&str], receiver_id: &AccountId) -> Vec<AirdropEntry> {
+        ids.iter()
+            .map(|id| AirdropEntry {
+                id: id.to_string(),
+                receiver_id: receiver_id.clone(),
+                item_type: "weapon".to_string(),
+                metadata: "{}".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_airdrop_campaign_runs_once() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.airdrop(
+            "launch".to_string(),
+            airdrop_entries(&["drop1", "drop2"], &accounts.alice),
+        );
+        let mut dropped = contract.get_items_by_owner(accounts.alice.clone());
+        dropped.sort();
+        assert_eq!(dropped, vec!["drop1", "drop2"]);
+        assert!(contract.is_campaign_processed("launch".to_string()));
+
+        // Re-submitting the campaign is rejected, even with fresh ids
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.airdrop(
+                "launch".to_string(),
+                airdrop_entries(&["drop3"], &accounts.bob),
+            );
+        }));
+        assert!(result.is_err());
+        assert!(contract.get_items_by_owner(accounts.bob.clone()).is_empty());
+
+        contract.airdrop(
+            "season-2".to_string(),
+            airdrop_entries(&["drop3"], &accounts.bob),
+        );
+        assert_eq!(contract.get_items_by_owner(accounts.bob), vec!["drop3"]);
+    }
+
+    #[test]
+    fn test_sale_royalties_accrue_until_withdrawn() {
+        let (mut contract, accounts) = setup_resale_contract();
+        give_items(&mut contract, &["item2"], &accounts.alice);
+        give_items(&mut contract, &["item3"], &accounts.bob);
+        testing_env!(get_context(accounts.owner.clone()).build());
+        contract.set_holder_royalty(Some(HolderRoyalty {
+            bps: 1000,
+            depth: 1,
+        }));
+
+        let mut context = get_context("carol.near".parse().unwrap());
+        testing_env!(context.attached_deposit(1000).build());
+        contract.buy_item("item1".to_string());
+        // Nothing is pushed to the royalty recipient during the sale
+        assert!(transfer_amounts_to(accounts.alice.as_str()).is_empty());
+        assert_eq!(contract.royalties_owed(accounts.alice.clone()), U128(100));
+
+        // A second sale adds to the same balance
+        contract.list_item("item1".to_string(), U128(500));
+        let mut context = get_context(accounts.bob.clone());
+        testing_env!(context.attached_deposit(500).build());
+        contract.buy_item("item1".to_string());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.bob
+        );
+        assert_eq!(contract.royalties_owed(accounts.alice.clone()), U128(100));
+        assert_eq!(contract.royalties_owed(accounts.bob.clone()), U128(50));
+
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.withdraw_royalties();
+        assert_eq!(transfer_amounts_to(accounts.alice.as_str()), vec![100]);
+        assert_eq!(contract.royalties_owed(accounts.alice.clone()), U128(0));
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.withdraw_royalties();
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_approvals_per_token_limit() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        mint_item(&mut contract, "item1");
+        contract.set_max_approvals_per_token(2);
+        let operators: Vec<AccountId> = ["op1.near", "op2.near", "op3.near"]
+            .iter()
+            .map(|id| id.parse().unwrap())
+            .collect();
+
+        contract.nft_approve("item1".to_string(), operators[0].clone(), None);
+        contract.nft_approve("item1".to_string(), operators[1].clone(), None);
+        // Re-approving an operator doesn't take another slot
+        contract.nft_approve("item1".to_string(), operators[1].clone(), None);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_approve("item1".to_string(), operators[2].clone(), None);
+        }));
+        assert!(result.is_err());
+        assert!(!contract.nft_is_approved("item1".to_string(), operators[2].clone(), None));
+
+        contract.nft_revoke("item1".to_string(), operators[0].clone());
+        contract.nft_approve("item1".to_string(), operators[2].clone(), None);
+        assert!(contract.nft_is_approved("item1".to_string(), operators[2].clone(), None));
+    }
+
+    #[test]
+    fn test_migrate_and_upgrade_require_self_call() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        env::state_write(&contract);
+
+        // The owner is an external account and is rejected like anyone else
+        let result = std::panic::catch_unwind(GameItems::migrate);
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.upgrade(Base64VecU8(vec![0]));
+        }));
+        assert!(result.is_err());
+
+        let contract_id = env::current_account_id();
+        testing_env!(get_context(contract_id).build());
+        let migrated = GameItems::migrate();
+        assert_eq!(migrated.get_item("item1".to_string()).owner_id, owner);
+    }
+
+    #[test]
+    fn test_can_transfer_reports_each_blocker() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in [
+            "free",
+            "locked",
+            "soulbound",
+            "parent",
+            "child",
+            "worn",
+            "spare",
+        ] {
+            mint_item(&mut contract, id);
+        }
+        let can = |contract: &GameItems, account: &AccountId, id: &str| {
+            contract.can_transfer(account.clone(), id.to_string())
+        };
+
+        assert!(can(&contract, &owner, "free"));
+        assert!(!can(&contract, &accounts.alice, "free"));
+        contract.nft_approve("free".to_string(), accounts.alice.clone(), None);
+        assert!(can(&contract, &accounts.alice, "free"));
+        assert!(!can(&contract, &owner, "missing"));
+
+        contract.lock_item("locked".to_string());
+        assert!(!can(&contract, &owner, "locked"));
+        contract.set_soulbound("soulbound".to_string(), true);
+        assert!(!can(&contract, &owner, "soulbound"));
+
+        contract.attach_child("parent".to_string(), "child".to_string());
+        assert!(!can(&contract, &owner, "child"));
+        assert!(can(&contract, &owner, "parent"));
+        contract.lock_item("child".to_string());
+        assert!(!can(&contract, &owner, "parent"));
+
+        contract.set_max_transfers(Some(1));
+        contract.transfer("worn".to_string(), accounts.alice.clone());
+        assert!(!can(&contract, &accounts.alice, "worn"));
+
+        contract.set_max_transfers(None);
+        contract.set_cooldown(TRANSFER_ACTION.to_string(), Some(1_000));
+        contract.transfer("free".to_string(), accounts.bob.clone());
+        assert!(!can(&contract, &owner, "spare"));
+        let mut context = get_context(owner.clone());
+        testing_env!(context.block_timestamp(1_000).build());
+        assert!(can(&contract, &owner, "spare"));
+    }
+
+    #[test]
+    fn test_can_burn_reports_each_blocker() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["free", "locked", "parent", "child"] {
+            mint_item(&mut contract, id);
+        }
+        let can = |contract: &GameItems, account: &AccountId, id: &str| {
+            contract.can_burn(account.clone(), id.to_string())
+        };
+
+        assert!(can(&contract, &owner, "free"));
+        // Approval lets an operator transfer, not burn
+        contract.nft_approve("free".to_string(), accounts.alice.clone(), None);
+        assert!(!can(&contract, &accounts.alice, "free"));
+        assert!(!can(&contract, &owner, "missing"));
+
+        contract.lock_item("locked".to_string());
+        assert!(!can(&contract, &owner, "locked"));
+
+        contract.attach_child("parent".to_string(), "child".to_string());
+        assert!(!can(&contract, &owner, "parent"));
+        assert!(!can(&contract, &owner, "child"));
+
+        contract.set_cooldown(REDEEM_ACTION.to_string(), Some(1_000));
+        contract.redeem("free".to_string());
+        contract.unlock_item("locked".to_string());
+        assert!(!can(&contract, &owner, "locked"));
+        let mut context = get_context(owner.clone());
+        testing_env!(context.block_timestamp(1_000).build());
+        assert!(can(&contract, &owner, "locked"));
+    }
+
+    #[test]
+    fn test_burn_refunds_freed_storage() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        let deposit = env::storage_byte_cost() * 10_000;
+        let mut context = get_context(accounts.alice.clone());
+        testing_env!(context.attached_deposit(deposit).build());
+        contract.storage_deposit(None);
+
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let storage_before = env::storage_usage();
+        contract.airdrop(
+            "launch".to_string(),
+            airdrop_entries(&["drop1"], &accounts.alice),
+        );
+        let used = env::storage_usage() - storage_before;
+        let charged = contract.storage_balance_of(accounts.alice.clone()).unwrap();
+        assert_eq!(charged.total, U128(deposit));
+        assert!(charged.available.0 < deposit);
+
+        testing_env!(get_context(accounts.alice.clone()).build());
+        let storage_before = env::storage_usage();
+        contract.redeem("drop1".to_string());
+        let freed = storage_before - env::storage_usage();
+        let refunded = contract.storage_balance_of(accounts.alice.clone()).unwrap();
+        assert_eq!(
+            refunded.available.0 - charged.available.0,
+            freed as Balance * env::storage_byte_cost()
+        );
+        // Indexes created for the first item stay behind, so less is freed than was used
+        assert!(freed <= used);
+
+        contract.storage_withdraw(None);
+        assert_eq!(
+            transfer_amounts_to(accounts.alice.as_str()),
+            vec![refunded.available.0]
+        );
+    }
+
+    #[test]
+    fn test_storage_refund_bounded_by_deposit() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        // Minted before alice deposited, so nothing was charged to her
+        mint_item(&mut contract, "item1");
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+
+        let mut context = get_context(accounts.alice.clone());
+        testing_env!(context.attached_deposit(1_000).build());
+        contract.storage_deposit(None);
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.redeem("item1".to_string());
+        assert_eq!(
+            contract.get_item_status("item1".to_string()),
+            ItemStatus::Unknown
+        );
+        assert_eq!(
+            contract.storage_balance_of(accounts.alice.clone()),
+            Some(StorageBalance {
+                total: U128(1_000),
+                available: U128(1_000),
+            })
+        );
+    }
+
+    #[test]
+    fn test_storage_refund_goes_to_the_payer() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        let deposit = env::storage_byte_cost() * 10_000;
+        for account in [&accounts.alice, &accounts.bob] {
+            let mut context = get_context(account.clone());
+            testing_env!(context.attached_deposit(deposit).build());
+            contract.storage_deposit(None);
+        }
+
+        testing_env!(get_context(owner).build());
+        contract.airdrop(
+            "launch".to_string(),
+            airdrop_entries(&["drop1"], &accounts.alice),
+        );
+        let charged = deposit
+            - contract
+                .storage_balance_of(accounts.alice.clone())
+                .unwrap()
+                .available
+                .0;
+        assert!(charged > 0);
+
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.transfer("drop1".to_string(), accounts.bob.clone());
+        testing_env!(get_context(accounts.bob.clone()).build());
+        contract.redeem("drop1".to_string());
+
+        // Bob didn't pay for the item, so its storage is credited back to alice
+        let bob = contract.storage_balance_of(accounts.bob.clone()).unwrap();
+        assert_eq!(bob.available, U128(deposit));
+        let alice = contract.storage_balance_of(accounts.alice.clone()).unwrap();
+        assert!(alice.available.0 > deposit - charged);
+        assert!(alice.available.0 <= deposit);
+    }
+
+    #[test]
+    fn test_creator_survives_transfers() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.transfer("item1".to_string(), accounts.bob.clone());
+
+        let item = contract.get_item("item1".to_string());
+        assert_eq!(item.owner_id, accounts.bob);
+        assert_eq!(item.creator_id, owner);
+
+        // A split-off stack keeps the original creator, not the holder who split it
+        testing_env!(get_context(owner.clone()).build());
+        contract.mint(
+            "arrows".to_string(),
+            "ammo".to_string(),
+            "{}".to_string(),
+            Some(10),
+            None,
+            None,
+            None,
+        );
+        contract.transfer("arrows".to_string(), accounts.alice.clone());
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.split("arrows".to_string(), "arrows2".to_string(), 4);
+        assert_eq!(contract.get_item("arrows2".to_string()).creator_id, owner);
+    }
+
+    #[test]
+    fn test_owns_all_and_owns_any() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["sword", "shield", "bow"] {
+            mint_item(&mut contract, id);
+        }
+        contract.transfer("bow".to_string(), accounts.alice.clone());
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        assert!(contract.owns_all(owner.clone(), ids(&["sword", "shield"])));
+        assert!(contract.owns_any(owner.clone(), ids(&["sword", "shield"])));
+
+        assert!(!contract.owns_all(owner.clone(), ids(&["sword", "bow"])));
+        assert!(contract.owns_any(owner.clone(), ids(&["sword", "bow"])));
+
+        assert!(!contract.owns_all(accounts.bob.clone(), ids(&["sword", "bow"])));
+        assert!(!contract.owns_any(accounts.bob.clone(), ids(&["sword", "bow"])));
+        assert!(!contract.owns_any(accounts.alice.clone(), ids(&["sword", "missing"])));
+    }
+
+    #[test]
+    fn test_every_event_carries_standard_and_schema_version() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+
+        let token_ids = vec!["item1".to_string()];
+        let events = vec![
+            Event::Mint(MintData {
+                owner_id: accounts.owner.clone(),
+                token_ids: token_ids.clone(),
+            }),
+            Event::Listing(ListingData {
+                token_id: "item1".to_string(),
+                seller_id: accounts.owner.clone(),
+                price: U128(500),
+            }),
+            Event::MintingLocked(MintingLockedData {
+                owner_id: accounts.owner.clone(),
+            }),
+            Event::Approve(ApprovalData {
+                owner_id: accounts.owner.clone(),
+                account_id: accounts.alice.clone(),
+                token_ids: token_ids.clone(),
+            }),
+            Event::Reveal(RevealData {
+                token_ids: token_ids.clone(),
+            }),
+            Event::Transfer(TransferData {
+                old_owner_id: accounts.owner.clone(),
+                new_owner_id: accounts.alice.clone(),
+                token_ids: token_ids.clone(),
+            }),
+            Event::MetadataUpdate(MetadataUpdateData {
+                token_ids: token_ids.clone(),
+            }),
+            Event::Rescue(RescueData {
+                new_owner_id: accounts.alice.clone(),
+                token_ids,
+            }),
+            Event::SupplyCorrected(SupplyCorrectedData {
+                old_total_minted: 3,
+                total_minted: 2,
+                total_burned: 0,
+                total_supply: 2,
+            }),
+        ];
+        for event in events {
+            emit_event(event);
+        }
+
+        let logged = logged_events();
+        assert_eq!(
+            logged
+                .iter()
+                .map(|event| event["event"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec![
+                "mint",
+                "listing",
+                "minting_locked",
+                "approve",
+                "reveal",
+                "transfer",
+                "metadata_update",
+                "rescue",
+                "supply_corrected"
+            ]
+        );
+        for event in &logged {
+            assert_eq!(event["standard"], events::EVENT_STANDARD);
+            assert_eq!(event["version"], events::EVENT_VERSION);
+            assert_eq!(
+                event["data"][0]["schema_version"],
+                events::EVENT_SCHEMA_VERSION
+            );
+        }
+        assert_eq!(logged[1]["data"][0]["price"], "500");
+    }
+
+    #[test]
+    fn test_transfer_recovers_from_missing_owner_index() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        // An item record written without its owner index entry, as a past bug could leave it
+        let item = GameItem::new(
+            "item1".to_string(),
+            owner.clone(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            ItemKind::Unique,
+        );
+        contract.items.insert(&item.id, &item);
+        assert!(contract.get_items_by_owner(owner.clone()).is_empty());
+
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.alice
+        );
+        assert_eq!(contract.get_items_by_owner(accounts.alice), vec!["item1"]);
+        assert!(contract.get_items_by_owner(owner).is_empty());
+        assert_eq!(
+            get_logs()[0],
+            "Item index of owner.near was missing and has been recreated"
+        );
+        assert_eq!(logged_events()[0]["event"], "transfer");
+    }
+
+    #[test]
+    fn test_recategorize_respects_type_cap() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.register_category("misc".to_string());
+        contract.set_type_cap("weapon".to_string(), Some(2));
+        for (id, item_type) in [
+            ("sword1", "weapon"),
+            ("sword2", "weapon"),
+            ("stick", "misc"),
+        ] {
+            contract.mint(
+                id.to_string(),
+                item_type.to_string(),
+                "{}".to_string(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.recategorize("stick".to_string(), "weapon".to_string());
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.type_supply("weapon".to_string()), (2, Some(2)));
+        assert_eq!(contract.type_supply("misc".to_string()), (1, None));
+
+        // Moving a weapon out frees its place under the cap
+        contract.recategorize("sword1".to_string(), "misc".to_string());
+        assert_eq!(contract.type_supply("weapon".to_string()), (1, Some(2)));
+        assert_eq!(contract.type_supply("misc".to_string()), (2, None));
+        contract.recategorize("stick".to_string(), "weapon".to_string());
+        assert_eq!(contract.type_supply("weapon".to_string()), (2, Some(2)));
+        assert_eq!(contract.get_item("stick".to_string()).item_type, "weapon");
+    }
+
+    #[test]
+    fn test_type_cap_limits_mints_per_type() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.set_type_cap("weapon".to_string(), Some(2));
+        mint_item(&mut contract, "sword1");
+        contract.airdrop(
+            "launch".to_string(),
+            airdrop_entries(&["sword2"], &accounts.alice),
+        );
+        assert_eq!(contract.type_supply("weapon".to_string()), (2, Some(2)));
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            mint_item(&mut contract, "sword3");
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.type_supply("weapon".to_string()), (2, Some(2)));
+
+        // Other types stay uncapped
+        for id in ["plate1", "plate2", "plate3"] {
+            contract.mint(
+                id.to_string(),
+                "armor".to_string(),
+                "{}".to_string(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+        assert_eq!(contract.type_supply("armor".to_string()), (3, None));
+
+        contract.set_type_cap("weapon".to_string(), None);
+        mint_item(&mut contract, "sword3");
+        assert_eq!(contract.type_supply("weapon".to_string()), (3, None));
+    }
+
+    #[test]
+    fn test_transfer_with_metadata_update() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        contract.nft_approve("item1".to_string(), accounts.bob.clone(), None);
+        contract.add_metadata_updater(accounts.bob.clone());
+
+        testing_env!(get_context(accounts.bob.clone()).build());
+        contract.transfer_with_metadata_update(
+            "item1".to_string(),
+            accounts.alice.clone(),
+            "{\"name\":\"Sword\",\"sold_by\":\"bob.near\"}".to_string(),
+        );
+        let item = contract.get_item("item1".to_string());
+        assert_eq!(item.owner_id, accounts.alice);
+        assert_eq!(
+            item.metadata,
+            "{\"name\":\"Sword\",\"sold_by\":\"bob.near\"}"
+        );
+        assert!(contract.verify_metadata("item1".to_string()));
+        assert_eq!(
+            logged_events(),
+            vec![
+                json!({
+                    "standard": "game_items",
+                    "version": "1.0.0",
+                    "event": "metadata_update",
+                    "data": [{"schema_version": 1, "token_ids": ["item1"]}],
+                }),
+                json!({
+                    "standard": "game_items",
+                    "version": "1.0.0",
+                    "event": "transfer",
+                    "data": [{
+                        "schema_version": 1,
+                        "old_owner_id": "owner.near",
+                        "new_owner_id": "alice.near",
+                        "token_ids": ["item1"],
+                    }],
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_holder_cannot_rewrite_metadata_by_transferring() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        mint_item(&mut contract, "item1");
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+
+        testing_env!(get_context(accounts.alice.clone()).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer_with_metadata_update(
+                "item1".to_string(),
+                accounts.bob.clone(),
+                "{\"name\":\"Excalibur\"}".to_string(),
+            );
+        }));
+        assert!(result.is_err());
+        let item = contract.get_item("item1".to_string());
+        assert_eq!(item.owner_id, accounts.alice);
+        assert_eq!(item.metadata, "{}");
+    }
+
+    #[test]
+    fn test_transfer_with_metadata_update_respects_metadata_lock() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        contract.lock_metadata("item1".to_string());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer_with_metadata_update(
+                "item1".to_string(),
+                accounts.alice.clone(),
+                "{\"stamped\":true}".to_string(),
+            );
+        }));
+        assert!(result.is_err());
+        let item = contract.get_item("item1".to_string());
+        assert_eq!(item.owner_id, owner);
+        assert_eq!(item.metadata, "{}");
+    }
+
+    #[test]
+    fn test_rarity_breakdown_for_owner() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        let rarities = [
+            ("dagger", Rarity::Common),
+            ("club", Rarity::Common),
+            ("bow", Rarity::Rare),
+            ("blade", Rarity::Legendary),
+        ];
+        for (id, rarity) in rarities {
+            contract.mint(
+                id.to_string(),
+                "weapon".to_string(),
+                "{}".to_string(),
+                None,
+                None,
+                None,
+                Some(rarity),
+            );
+        }
+
+        let tally = contract.rarity_breakdown_for_owner(owner.clone(), None, None);
+        assert_eq!(
+            tally,
+            HashMap::from([
+                (Rarity::Common, 2),
+                (Rarity::Rare, 1),
+                (Rarity::Legendary, 1)
+            ])
+        );
+        let first_page = contract.rarity_breakdown_for_owner(owner, None, Some(3));
+        assert_eq!(first_page.values().sum::<u64>(), 3);
+
+        assert!(contract
+            .rarity_breakdown_for_owner(accounts.alice, None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_contract_owner_transfers_only_items_it_holds() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["item1", "item2", "item3"] {
+            mint_item(&mut contract, id);
+        }
+
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        contract.transfer("item2".to_string(), accounts.bob.clone());
+        assert_eq!(contract.get_items_by_owner(owner.clone()), vec!["item3"]);
+        assert_eq!(
+            contract.export_owners(None, None),
+            vec![
+                (accounts.alice.clone(), 1),
+                (accounts.bob.clone(), 1),
+                (owner.clone(), 1)
+            ]
+        );
+
+        // Holding the contract doesn't let the owner move a player's item back
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("item1".to_string(), owner.clone());
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.alice
+        );
+        assert_eq!(contract.get_items_by_owner(owner.clone()), vec!["item3"]);
+
+        contract.transfer("item3".to_string(), accounts.alice.clone());
+        assert!(contract.get_items_by_owner(owner).is_empty());
+        let mut alice_items = contract.get_items_by_owner(accounts.alice);
+        alice_items.sort();
+        assert_eq!(alice_items, vec!["item1", "item3"]);
+    }
+
+    #[test]
+    fn test_rental_reclaim_waits_for_grace_period() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        contract.set_rental_policy(500, U128(0));
+        contract.rent_out("item1".to_string(), accounts.alice.clone(), 1_000);
+        assert_eq!(
+            contract.get_rental("item1".to_string()),
+            Some(Rental {
+                renter_id: accounts.alice.clone(),
+                until_ns: 1_000,
+            })
+        );
+        assert!(!contract.can_transfer(owner.clone(), "item1".to_string()));
+
+        // Past the rental but still within the grace period
+        let mut context = get_context(owner.clone());
+        testing_env!(context.block_timestamp(1_200).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.reclaim("item1".to_string());
+        }));
+        assert!(result.is_err());
+        assert!(contract.get_rental("item1".to_string()).is_some());
+
+        let mut context = get_context(owner.clone());
+        testing_env!(context.block_timestamp(1_500).build());
+        contract.reclaim("item1".to_string());
+        assert_eq!(contract.get_rental("item1".to_string()), None);
+        contract.transfer("item1".to_string(), accounts.bob);
+    }
+
+    #[test]
+    fn test_rented_stack_cannot_be_split_or_merged() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        mint_stack(&mut contract, "arrows", 50);
+        mint_stack(&mut contract, "quiver", 10);
+        contract.rent_out("arrows".to_string(), accounts.alice.clone(), 1_000);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.split("arrows".to_string(), "arrows2".to_string(), 20);
+        }));
+        assert!(result.is_err());
+        for (into_id, from_id) in [("quiver", "arrows"), ("arrows", "quiver")] {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.merge(into_id.to_string(), from_id.to_string());
+            }));
+            assert!(result.is_err());
+        }
+        assert_eq!(contract.get_item("arrows".to_string()).quantity(), 50);
+        assert_eq!(contract.get_item("quiver".to_string()).quantity(), 10);
+        assert!(contract.get_rental("arrows".to_string()).is_some());
+    }
+
+    #[test]
+    fn test_renter_extends_rental_for_a_fee() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        contract.set_rental_policy(500, U128(100));
+        contract.rent_out("item1".to_string(), accounts.alice.clone(), 1_000);
+
+        let mut context = get_context(accounts.bob.clone());
+        testing_env!(context.attached_deposit(100).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.renter_extend("item1".to_string(), 1_000);
+        }));
+        assert!(result.is_err());
+
+        let mut context = get_context(accounts.alice.clone());
+        testing_env!(context.attached_deposit(150).block_timestamp(900).build());
+        contract.renter_extend("item1".to_string(), 1_000);
+        assert_eq!(
+            contract.get_rental("item1".to_string()).unwrap().until_ns,
+            2_000
+        );
+        assert_eq!(transfer_amounts_to(owner.as_str()), vec![100]);
+        assert_eq!(transfer_amounts_to(accounts.alice.as_str()), vec![50]);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.renter_extend("item1".to_string(), MAX_RENTAL_NS);
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.renter_extend("item1".to_string(), u64::MAX);
+        }));
+        assert!(result.is_err());
+        // A grace period reaching past the end of time never lets the rental be reclaimed
+        testing_env!(get_context(owner.clone()).build());
+        contract.set_rental_policy(u64::MAX, U128(100));
+        let mut context = get_context(owner.clone());
+        testing_env!(context.block_timestamp(u64::MAX - 1).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.reclaim("item1".to_string());
+        }));
+        assert!(result.is_err());
+        testing_env!(get_context(owner.clone()).build());
+        contract.set_rental_policy(500, U128(100));
+        assert_eq!(
+            contract.get_rental("item1".to_string()).unwrap().until_ns,
+            2_000
+        );
+
+        // The old deadline plus grace has passed, the extended one hasn't
+        let mut context = get_context(owner);
+        testing_env!(context.block_timestamp(1_600).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.reclaim("item1".to_string());
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_owners_with_counts_tracks_holders() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["item1", "item2", "item3"] {
+            mint_item(&mut contract, id);
+        }
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        contract.transfer("item2".to_string(), accounts.alice.clone());
+        assert_eq!(
+            contract.owners_with_counts(None, None),
+            vec![(accounts.alice.clone(), 2), (owner.clone(), 1)]
+        );
+        assert_eq!(contract.unique_owners(), 2);
+
+        // The owner drops to zero items and leaves the enumeration
+        contract.transfer("item3".to_string(), accounts.bob.clone());
+        assert_eq!(
+            contract.owners_with_counts(None, None),
+            vec![(accounts.alice.clone(), 2), (accounts.bob.clone(), 1)]
+        );
+        assert_eq!(
+            contract.owners_with_counts(Some(accounts.alice.clone()), Some(1)),
+            vec![(accounts.bob, 1)]
+        );
+        assert_eq!(contract.unique_owners(), 2);
+    }
+
+    #[test]
+    fn test_provenance_hash_chains_transfers() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        mint_item(&mut contract, "item2");
+        // Untracked transfers leave the hash alone
+        contract.transfer("item2".to_string(), accounts.alice.clone());
+        assert_eq!(contract.provenance_hash("item2".to_string()), Some([0; 32]));
+        assert_eq!(contract.provenance_hash("missing".to_string()), None);
+
+        contract.set_provenance_tracking(true);
+        let mut context = get_context(owner.clone());
+        testing_env!(context.block_timestamp(1_000).build());
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        let first = next_provenance_hash(&[0; 32], &owner, &accounts.alice, 1_000);
+        assert_eq!(contract.provenance_hash("item1".to_string()), Some(first));
+        let preimage = [
+            &[0; 32][..],
+            b"owner.near",
+            b"alice.near",
+            &1_000u64.to_le_bytes(),
+        ]
+        .concat();
+        assert_eq!(first.to_vec(), env::sha256(&preimage));
+
+        let mut context = get_context(accounts.alice.clone());
+        testing_env!(context.block_timestamp(2_000).build());
+        contract.transfer("item1".to_string(), accounts.bob.clone());
+        let second = next_provenance_hash(&first, &accounts.alice, &accounts.bob, 2_000);
+        assert_eq!(contract.provenance_hash("item1".to_string()), Some(second));
+        assert_ne!(first, second);
+
+        // The same hop at another time gives a different link
+        assert_ne!(
+            next_provenance_hash(&first, &accounts.alice, &accounts.bob, 2_001),
+            second
+        );
+    }
+
+    #[test]
+    fn test_minters_limited_to_granted_types() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+        let studio: AccountId = "studio.near".parse().unwrap();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        contract.grant_mint_types(studio.clone(), vec!["weapon".to_string()]);
+        assert_eq!(contract.get_mint_types(studio.clone()), vec!["weapon"]);
+
+        testing_env!(get_context(studio.clone()).build());
+        mint_item(&mut contract, "sword");
+        let item = contract.get_item("sword".to_string());
+        assert_eq!(item.owner_id, studio);
+        assert_eq!(item.creator_id, studio);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.mint(
+                "plate".to_string(),
+                "armor".to_string(),
+                "{}".to_string(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_item_status("plate".to_string()),
+            ItemStatus::Unknown
+        );
+
+        // The contract owner needs no grant
+        testing_env!(get_context(owner.clone()).build());
+        contract.mint(
+            "plate".to_string(),
+            "armor".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(contract.get_item("plate".to_string()).owner_id, owner);
+
+        contract.revoke_mint_types(studio.clone(), vec!["weapon".to_string()]);
+        testing_env!(get_context(studio).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            mint_item(&mut contract, "axe");
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_transfer_if_unchanged() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        mint_item(&mut contract, "item2");
+        let advertised = contract.get_item("item1".to_string()).content_hash;
+
+        contract.transfer_if_unchanged("item1".to_string(), accounts.alice.clone(), advertised);
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.alice
+        );
+
+        // The metadata is swapped after the hash was advertised
+        let advertised = contract.get_item("item2".to_string()).content_hash;
+        contract.update_metadata("item2".to_string(), "{\"power\":1}".to_string());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer_if_unchanged("item2".to_string(), accounts.alice.clone(), advertised);
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.get_item("item2".to_string()).owner_id, owner);
+    }
+
+    #[test]
+    fn test_locked_fields_reject_changes() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{\"media\":\"https://a.png\",\"power\":10}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_locked_fields(vec!["media".to_string()]);
+
+        contract.update_metadata(
+            "item1".to_string(),
+            "{\"media\":\"https://a.png\",\"power\":11}".to_string(),
+        );
+        contract.patch_metadata("item1".to_string(), "{\"level\":2}".to_string());
+        assert_eq!(
+            contract.get_metadata("item1".to_string()).unwrap(),
+            "{\"level\":2,\"media\":\"https://a.png\",\"power\":11}"
+        );
+
+        let attempts = [
+            "{\"media\":\"https://b.png\",\"power\":11}",
+            // Dropping a locked key changes it too
+            "{\"power\":12}",
+        ];
+        for metadata in attempts {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.update_metadata("item1".to_string(), metadata.to_string());
+            }));
+            assert!(result.is_err());
+        }
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.patch_metadata(
+                "item1".to_string(),
+                "{\"media\":\"https://b.png\"}".to_string(),
+            );
+        }));
+        assert!(result.is_err());
+        assert!(contract
+            .get_metadata("item1".to_string())
+            .unwrap()
+            .contains("https://a.png"));
+    }
+
+    #[test]
+    fn test_batch_reveal_skips_revealed_items() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["box1", "box2", "box3"] {
+            mint_item(&mut contract, id);
+        }
+        contract.reveal("box2".to_string(), "{\"name\":\"Axe\"}".to_string());
+
+        testing_env!(get_context(owner).build());
+        let skipped = contract.batch_reveal(vec![
+            ("box1".to_string(), "{\"name\":\"Sword\"}".to_string()),
+            ("box2".to_string(), "{\"name\":\"Stick\"}".to_string()),
+            ("box3".to_string(), "{\"name\":\"Bow\"}".to_string()),
+        ]);
+        assert_eq!(skipped, vec!["box2"]);
+        assert_eq!(
+            contract.get_metadata("box2".to_string()).unwrap(),
+            "{\"name\":\"Axe\"}"
+        );
+        assert_eq!(
+            contract.get_metadata("box3".to_string()).unwrap(),
+            "{\"name\":\"Bow\"}"
+        );
+        assert!(contract.get_item("box1".to_string()).revealed);
+        let logged = logged_events();
+        assert_eq!(
+            logged
+                .iter()
+                .map(|event| event["event"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["metadata_update", "metadata_update", "reveal"]
+        );
+        assert_eq!(
+            logged[2],
+            json!({
+                "standard": "game_items",
+                "version": "1.0.0",
+                "event": "reveal",
+                "data": [{"schema_version": 1, "token_ids": ["box1", "box3"]}],
+            })
+        );
+    }
+
+    #[test]
+    fn test_receive_blocked_account_can_only_send() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        mint_item(&mut contract, "item2");
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        contract.block_receiving(accounts.alice.clone());
+        assert!(contract.is_receive_blocked(accounts.alice.clone()));
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("item2".to_string(), accounts.alice.clone());
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.airdrop(
+                "launch".to_string(),
+                airdrop_entries(&["drop1"], &accounts.alice),
+            );
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_items_by_owner(accounts.alice.clone()),
+            vec!["item1"]
+        );
+
+        // Existing holdings can still leave
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.transfer("item1".to_string(), accounts.bob.clone());
+        assert!(contract
+            .get_items_by_owner(accounts.alice.clone())
+            .is_empty());
+
+        testing_env!(get_context(owner).build());
+        contract.unblock_receiving(accounts.alice.clone());
+        contract.transfer("item2".to_string(), accounts.alice.clone());
+        assert_eq!(contract.get_items_by_owner(accounts.alice), vec!["item2"]);
+    }
+
+    #[test]
+    fn test_listing_price_bounds() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner, None);
+        mint_item(&mut contract, "item1");
+        assert_eq!(contract.price_bounds(), (U128(0), U128(0)));
+        contract.list_item("item1".to_string(), U128(1));
+        contract.list_item("item1".to_string(), U128(u128::MAX));
+
+        contract.set_price_bounds(U128(100), U128(1_000));
+        contract.list_item("item1".to_string(), U128(500));
+        for price in [99, 1_001] {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.list_item("item1".to_string(), U128(price));
+            }));
+            assert!(result.is_err());
+        }
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.mint_and_list(
+                "item2".to_string(),
+                "weapon".to_string(),
+                "{}".to_string(),
+                U128(5_000),
+            );
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_listing("item1".to_string()).unwrap().price,
+            U128(500)
+        );
+
+        // Only a floor
+        contract.set_price_bounds(U128(100), U128(0));
+        contract.list_item("item1".to_string(), U128(u128::MAX));
+    }
+
+    #[test]
+    fn test_paged_views_keep_order_across_mints() {
+        let owner = get_accounts().owner;
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["b", "d", "f", "h"] {
+            mint_item(&mut contract, id);
+        }
+        // Burning swaps the set's last member into the freed slot
+        contract.redeem("b".to_string());
+        mint_item(&mut contract, "b");
+        let ids = |items: Vec<GameItem>| items.into_iter().map(|item| item.id).collect::<Vec<_>>();
+
+        let first = ids(contract.items_by_type("weapon".to_string(), None, Some(2)));
+        assert_eq!(first, vec!["b", "d"]);
+        // Inserted behind the cursor, so neither "d" repeats nor "f" is skipped
+        mint_item(&mut contract, "a");
+        mint_item(&mut contract, "g");
+        let after = Some("d".to_string());
+        let second = ids(contract.items_by_type("weapon".to_string(), after, Some(3)));
+        assert_eq!(second, vec!["f", "g", "h"]);
+
+        let first = ids(contract.items_by_rarity(Rarity::Common, None, Some(2)));
+        assert_eq!(first, vec!["a", "b"]);
+        mint_item(&mut contract, "c");
+        let after = Some("b".to_string());
+        let second = ids(contract.items_by_rarity(Rarity::Common, after, Some(2)));
+        assert_eq!(second, vec!["c", "d"]);
+        assert!(contract
+            .items_by_type("armor".to_string(), None, None)
+            .is_empty());
+        assert_eq!(
+            contract.get_items_by_owner(owner.clone()),
+            vec!["a", "b", "c", "d", "f", "g", "h"]
+        );
+        assert_eq!(
+            contract.items_for_owners(vec![owner], Some(U128(5)), None),
+            vec!["g", "h"]
+        );
+    }
+
+    #[test]
+    fn test_rescue_item_held_by_the_contract() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        contract.transfer("item1".to_string(), env::current_account_id());
+
+        testing_env!(get_context(accounts.owner.clone()).build());
+        contract.rescue_to("item1".to_string(), accounts.bob.clone());
+        assert_eq!(
+            contract.get_item("item1".to_string()).owner_id,
+            accounts.bob
+        );
+        assert!(contract
+            .get_items_by_owner(env::current_account_id())
+            .is_empty());
+
+        let logged = logged_events();
+        assert_eq!(logged.len(), 2);
+        assert_eq!(logged[0]["event"], "transfer");
+        assert_eq!(logged[1]["event"], "rescue");
+        assert_eq!(logged[1]["data"][0]["new_owner_id"], "bob.near");
+        assert_eq!(logged[1]["data"][0]["token_ids"][0], "item1");
+    }
+
+    #[test]
+    #[should_panic(expected = "Only items held by the contract can be rescued")]
+    fn test_rescue_rejects_items_not_held_by_the_contract() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        contract.transfer("item1".to_string(), accounts.bob.clone());
+
+        contract.rescue_to("item1".to_string(), accounts.owner.clone());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the contract owner can call this method")]
+    fn test_rescue_requires_contract_owner() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        contract.transfer("item1".to_string(), env::current_account_id());
+
+        testing_env!(get_context(accounts.bob.clone()).build());
+        contract.rescue_to("item1".to_string(), accounts.bob.clone());
+    }
+
+    #[test]
+    fn test_nft_token_includes_royalty_and_payout() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        let royalty = HashMap::from([(accounts.alice.clone(), 1_000)]);
+        contract.mint(
+            "item1".to_string(),
+            "weapon".to_string(),
+            "{}".to_string(),
+            None,
+            Some(royalty.clone()),
+            None,
+            None,
+        );
+
+        let token = contract.nft_token("item1".to_string()).unwrap();
+        assert_eq!(token.royalty, Some(royalty));
+        let json = near_sdk::serde_json::to_value(&token).unwrap();
+        assert_eq!(json["token_id"], "item1");
+        assert_eq!(json["royalty"]["alice.near"], 1_000);
+
+        // The creator stays on the token after it changes hands
+        contract.transfer("item1".to_string(), accounts.bob.clone());
+        let token = contract.nft_token("item1".to_string()).unwrap();
+        assert_eq!(token.owner_id, accounts.bob);
+        assert_eq!(token.creator_id, accounts.owner);
+        let json = near_sdk::serde_json::to_value(&token).unwrap();
+        assert_eq!(json["creator_id"], accounts.owner.as_str());
+
+        let payout = contract.nft_payout("item1".to_string(), U128(10_000), Some(2));
+        assert_eq!(
+            payout.payout,
+            HashMap::from([
+                (accounts.alice.clone(), U128(1_000)),
+                (accounts.bob.clone(), U128(9_000)),
+            ])
+        );
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_payout("item1".to_string(), U128(10_000), Some(1))
+        }));
+        assert!(result.is_err());
+        assert!(contract.nft_token("missing".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_nft_token_omits_missing_royalty() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        mint_item(&mut contract, "item1");
+
+        let token = contract.nft_token("item1".to_string()).unwrap();
+        assert_eq!(token.royalty, None);
+        let json = near_sdk::serde_json::to_value(&token).unwrap();
+        assert!(json.get("royalty").is_none());
+        assert_eq!(
+            contract
+                .nft_payout("item1".to_string(), U128(500), None)
+                .payout,
+            HashMap::from([(accounts.owner.clone(), U128(500))])
+        );
+    }
+
+    #[test]
+    fn test_recount_corrects_inflated_supply() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        for id in ["item1", "item2", "item3"] {
+            mint_item(&mut contract, id);
+        }
+        let item3 = contract.items.get(&"item3".to_string()).unwrap();
+        contract.internal_burn(item3);
+        contract.total_minted += 5;
+        // An id left behind without its record
+        contract.item_ids.insert(&"item0".to_string(), &());
+        assert_eq!(contract.supply_breakdown(), (8, 3, 1));
+
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let progress = contract.recount_supply(None, Some(2));
+        assert_eq!(
+            progress,
+            RecountProgress {
+                counted: 1,
+                next: Some("item1".to_string())
+            }
+        );
+        // Pages have to follow on from each other
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.recount_supply(Some("item0".to_string()), Some(1))
+        }));
+        assert!(result.is_err());
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| contract.finalize_recount()));
+        assert!(result.is_err());
+
+        let progress = contract.recount_supply(Some("item1".to_string()), Some(2));
+        assert_eq!(
+            progress,
+            RecountProgress {
+                counted: 2,
+                next: None
+            }
+        );
+        assert!(contract.finalize_recount());
+        assert_eq!(contract.supply_breakdown(), (3, 2, 1));
+        assert!(!contract.item_ids.contains_key(&"item0".to_string()));
+
+        let logged = logged_events();
+        assert_eq!(logged.len(), 1);
+        assert_eq!(logged[0]["event"], "supply_corrected");
+        assert_eq!(logged[0]["data"][0]["old_total_minted"], 8);
+        assert_eq!(logged[0]["data"][0]["total_supply"], 2);
+
+        // A consistent contract is left untouched
+        testing_env!(get_context(accounts.owner.clone()).build());
+        contract.recount_supply(None, None);
+        assert!(!contract.finalize_recount());
+        assert!(logged_events().is_empty());
+    }
+
+    #[test]
+    fn test_recount_is_void_after_supply_changes() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        mint_item(&mut contract, "item2");
+
+        testing_env!(get_context(accounts.owner.clone()).build());
+        assert_eq!(contract.recount_supply(None, None).next, None);
+        // Minted behind the cursor, so the pass never saw it
+        mint_item(&mut contract, "item1");
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| contract.finalize_recount()));
+        assert!(result.is_err());
+
+        contract.recount_supply(None, None);
+        assert!(!contract.finalize_recount());
+        assert_eq!(contract.supply_breakdown(), (2, 2, 0));
+    }
+
+    #[test]
+    fn test_configured_gas_is_attached_to_hooks() {
+        let (mut contract, accounts) = setup_transfer_call_contract();
+        let observer: AccountId = "observer.near".parse().unwrap();
+        contract.set_observer(Some(observer.clone()));
+        let (on_transfer, resolve, on_item) = (
+            Gas(40_000_000_000_000),
+            Gas(15_000_000_000_000),
+            Gas(2_000_000_000_000),
+        );
+        contract.set_cross_contract_gas(on_transfer, resolve, on_item);
+        assert_eq!(
+            contract.cross_contract_gas(),
+            (on_transfer, resolve, on_item)
+        );
+
+        contract.nft_transfer_call(
+            "market.near".parse().unwrap(),
+            "item1".to_string(),
+            "list".to_string(),
+        );
+        assert_eq!(
+            function_calls_to("market.near"),
+            vec![("nft_on_transfer".to_string(), on_transfer)]
+        );
+        assert!(function_calls_to(env::current_account_id().as_str())
+            .contains(&("nft_resolve_transfer".to_string(), resolve)));
+        assert_eq!(
+            function_calls_to(observer.as_str()),
+            vec![("on_item_transfer".to_string(), on_item)]
+        );
+        assert!(contract.get_items_by_owner(accounts.owner).is_empty());
+    }
+
+    #[test]
+    fn test_out_of_range_gas_is_rejected() {
+        let (mut contract, _) = setup_transfer_call_contract();
+        let defaults = contract.cross_contract_gas();
+        for (on_transfer, resolve, on_item) in [
+            (
+                Gas(101_000_000_000_000),
+                Gas(10_000_000_000_000),
+                Gas(5_000_000_000_000),
+            ),
+            (Gas(25_000_000_000_000), Gas(0), Gas(5_000_000_000_000)),
+            (
+                Gas(25_000_000_000_000),
+                Gas(10_000_000_000_000),
+                Gas(500_000_000_000_000),
+            ),
+        ] {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.set_cross_contract_gas(on_transfer, resolve, on_item)
+            }));
+            assert!(result.is_err());
+        }
+        assert_eq!(contract.cross_contract_gas(), defaults);
+    }
+
+    fn mint_stack(contract: &mut GameItems, id: &str, quantity: u64) {
+        contract.mint(
+            id.to_string(),
+            "ammo".to_string(),
+            "{}".to_string(),
+            Some(quantity),
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_transfer_quantity_moves_unique_item_whole() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        mint_item(&mut contract, "sword");
+        assert_eq!(
+            contract.get_item("sword".to_string()).kind,
+            ItemKind::Unique
+        );
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer_quantity(
+                "sword".to_string(),
+                accounts.alice.clone(),
+                2,
+                "sword2".to_string(),
+            )
+        }));
+        assert!(result.is_err());
+
+        contract.transfer_quantity(
+            "sword".to_string(),
+            accounts.alice.clone(),
+            1,
+            "sword2".to_string(),
+        );
+        assert_eq!(
+            contract.get_item("sword".to_string()).owner_id,
+            accounts.alice
+        );
+        assert_eq!(
+            contract.get_item_status("sword2".to_string()),
+            ItemStatus::Unknown
+        );
+        assert_eq!(contract.supply_breakdown(), (1, 1, 0));
+    }
+
+    #[test]
+    fn test_partial_stack_transfer_splits_across_owners() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        mint_stack(&mut contract, "arrows", 50);
+
+        contract.transfer_quantity(
+            "arrows".to_string(),
+            accounts.alice.clone(),
+            20,
+            "arrows-alice".to_string(),
+        );
+        let kept = contract.get_item("arrows".to_string());
+        assert_eq!(kept.kind, ItemKind::Stackable { quantity: 30 });
+        assert_eq!(kept.owner_id, accounts.owner);
+        let sent = contract.get_item("arrows-alice".to_string());
+        assert_eq!(sent.kind, ItemKind::Stackable { quantity: 20 });
+        assert_eq!(sent.owner_id, accounts.alice);
+        assert_eq!(sent.transfer_count, 1);
+        assert_eq!(sent.transfer_log, vec![accounts.owner.clone()]);
+
+        assert_eq!(
+            contract.get_items_by_owner(accounts.owner.clone()),
+            vec!["arrows".to_string()]
+        );
+        assert_eq!(
+            contract.get_items_by_owner(accounts.alice.clone()),
+            vec!["arrows-alice".to_string()]
+        );
+        assert_eq!(
+            ids_of(contract.nft_tokens(None, None)),
+            vec!["arrows", "arrows-alice"]
+        );
+
+        // A locked stack can't be split off to someone else either
+        contract.lock_item("arrows".to_string());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer_quantity(
+                "arrows".to_string(),
+                accounts.bob.clone(),
+                5,
+                "arrows-bob".to_string(),
+            )
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            contract.get_item_status("arrows-bob".to_string()),
+            ItemStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn test_stackable_items_merge_but_unique_ones_do_not() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        mint_stack(&mut contract, "arrows", 30);
+        mint_stack(&mut contract, "quiver", 12);
+        contract.mint(
+            "bow".to_string(),
+            "ammo".to_string(),
+            "{}".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        contract.merge("arrows".to_string(), "quiver".to_string());
+        assert_eq!(
+            contract.get_item("arrows".to_string()).kind,
+            ItemKind::Stackable { quantity: 42 }
+        );
+        assert_eq!(
+            contract.get_items_by_owner(accounts.owner.clone()),
+            vec!["arrows".to_string(), "bow".to_string()]
+        );
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.merge("arrows".to_string(), "bow".to_string())
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.split("bow".to_string(), "bow2".to_string(), 1)
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.get_item("bow".to_string()).kind, ItemKind::Unique);
+    }
+
+    #[test]
+    fn test_every_transfer_and_metadata_change_is_logged() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+
+        testing_env!(get_context(owner.clone()).build());
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        contract.force_transfer("item1".to_string(), accounts.bob.clone());
+        contract.update_metadata("item1".to_string(), "{\"name\":\"Axe\"}".to_string());
+        contract.patch_metadata("item1".to_string(), "{\"level\":2}".to_string());
+
+        let logged = logged_events();
+        assert_eq!(
+            logged
+                .iter()
+                .map(|event| event["event"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["transfer", "transfer", "metadata_update", "metadata_update"]
+        );
+        assert_eq!(logged[0]["data"][0]["old_owner_id"], "owner.near");
+        assert_eq!(logged[0]["data"][0]["new_owner_id"], "alice.near");
+        assert_eq!(logged[1]["data"][0]["old_owner_id"], "alice.near");
+        assert_eq!(logged[1]["data"][0]["new_owner_id"], "bob.near");
+        assert_eq!(logged[3]["data"][0]["token_ids"][0], "item1");
+    }
+
+    #[test]
+    fn test_sweep_account_skips_items_with_blocked_children() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        for id in ["parent", "child", "other"] {
+            mint_item(&mut contract, id);
+            contract.transfer(id.to_string(), accounts.alice.clone());
+        }
+        testing_env!(get_context(accounts.alice.clone()).build());
+        contract.attach_child("parent".to_string(), "child".to_string());
+        contract.lock_item("child".to_string());
+
+        testing_env!(get_context(owner.clone()).build());
+        assert_eq!(
+            contract.sweep_account(accounts.alice.clone(), accounts.bob.clone(), None),
+            SweepResult {
+                moved: 1,
+                remaining: 2
+            }
+        );
+        assert_eq!(
+            contract.get_items_by_owner(accounts.bob),
+            vec!["other".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_transfer_with_signature_respects_cooldown_and_pull_transfers() {
+        let accounts = get_accounts();
+        let owner = accounts.owner.clone();
+        let relayer: AccountId = "relayer.near".parse().unwrap();
+
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = new_contract(owner.clone(), None);
+        mint_item(&mut contract, "item1");
+        mint_item(&mut contract, "item2");
+        contract.transfer("item1".to_string(), accounts.alice.clone());
+        contract.transfer("item2".to_string(), accounts.alice.clone());
+        contract.set_cooldown(TRANSFER_ACTION.to_string(), Some(1_000));
+        let keypair = register_signing_key(&mut contract, &accounts.alice, 7);
+
+        testing_env!(get_context(relayer.clone()).build());
+        let signature = sign_transfer(&contract, &keypair, "item1", &accounts.bob, 1);
+        contract.transfer_with_signature("item1".to_string(), accounts.bob.clone(), 1, signature);
+        // Alice's cooldown now also stops her own transfers
+        testing_env!(get_context(accounts.alice.clone()).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer("item2".to_string(), accounts.bob.clone())
+        }));
+        assert!(result.is_err());
+
+        // And the next relayed transfer waits for it too
+        testing_env!(get_context(relayer.clone()).build());
+        let signature = sign_transfer(&contract, &keypair, "item2", &accounts.bob, 2);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer_with_signature(
+                "item2".to_string(),
+                accounts.bob.clone(),
+                2,
+                signature.clone(),
+            )
+        }));
+        assert!(result.is_err());
+
+        testing_env!(get_context(owner).build());
+        contract.set_pull_transfers(true);
+        testing_env!(get_context(relayer).block_timestamp(2_000).build());
+        contract.transfer_with_signature("item2".to_string(), accounts.bob.clone(), 2, signature);
+        assert_eq!(
+            contract.get_item("item2".to_string()).owner_id,
+            accounts.alice
+        );
+        assert_eq!(
+            contract.get_pending_transfer(accounts.bob, "item2".to_string()),
+            Some(accounts.alice)
+        );
+    }
+
+    #[test]
+    fn test_locked_minting_blocks_bundles_and_splits() {
+        let accounts = get_accounts();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        let mut contract = new_contract(accounts.owner.clone(), None);
+        mint_stack(&mut contract, "arrows", 10);
+        mint_item(&mut contract, "item1");
+        contract.lock_minting();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.split("arrows".to_string(), "arrows2".to_string(), 4)
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer_quantity(
+                "arrows".to_string(),
+                accounts.alice.clone(),
+                4,
+                "arrows2".to_string(),
+            )
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.bundle(
+                vec!["item1".to_string()],
+                "bundle1".to_string(),
+                "{}".to_string(),
+            )
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.get_item("arrows".to_string()).quantity(), 10);
+        assert_eq!(contract.supply_breakdown(), (2, 2, 0));
+
+        // Moving a whole stack creates nothing and still works
+        contract.transfer_quantity(
+            "arrows".to_string(),
+            accounts.alice.clone(),
+            10,
+            "arrows2".to_string(),
+        );
+        assert_eq!(
+            contract.get_item("arrows".to_string()).owner_id,
+            accounts.alice
+        );
+    }
+
+    #[test]
+    fn test_holder_royalty_skips_holders_without_items() {
+        let (mut contract, accounts) = setup_resale_contract();
+        testing_env!(get_context(accounts.owner.clone()).build());
+        contract.set_holder_royalty(Some(HolderRoyalty {
+            bps: 1000,
+            depth: 2,
+        }));
+        assert_eq!(
+            contract
+                .nft_payout("item1".to_string(), U128(1000), None)
+                .payout,
+            HashMap::from([(accounts.bob.clone(), U128(1000))])
+        );
+
+        give_items(&mut contract, &["item2"], &accounts.alice);
+        assert_eq!(
+            contract
+                .nft_payout("item1".to_string(), U128(1000), None)
+                .payout,
+            HashMap::from([
+                (accounts.alice.clone(), U128(100)),
+                (accounts.bob.clone(), U128(900)),
+            ])
+        );
     }
 }